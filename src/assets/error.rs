@@ -0,0 +1,123 @@
+use std::{
+    error::Error,
+    fmt::{self, Display, Formatter},
+    io,
+    path::{Path, PathBuf},
+};
+
+use image::ImageError;
+
+/// Something wrong with one of the files of a pack.
+#[derive(Debug)]
+pub enum PackError {
+    /// A file or directory could not be read.
+    Io { path: PathBuf, error: io::Error },
+
+    /// A file is not valid TOML or lacks some field. Position is the line and
+    /// column, both starting at 1, when known.
+    Syntax {
+        path: PathBuf,
+        position: Option<(usize, usize)>,
+        message: String,
+    },
+
+    /// A tile could not be decoded.
+    Image { path: PathBuf, error: ImageError },
+
+    /// A tile is not a single atlas cell.
+    TileSize { path: PathBuf, width: u32, height: u32 },
+
+    /// A file refers to a tile, texture, model, block or structure the pack
+    /// does not have.
+    Missing {
+        path: PathBuf,
+        kind: &'static str,
+        name: String,
+    },
+
+    /// A file is well formed but makes no sense, such as a palette key longer
+    /// than a character.
+    Invalid { path: PathBuf, message: String },
+}
+
+impl PackError {
+    pub fn io(path: &Path, error: io::Error) -> Self {
+        Self::Io {
+            path: path.to_path_buf(),
+            error,
+        }
+    }
+
+    pub fn syntax(path: &Path, error: toml::de::Error) -> Self {
+        let position = error.line_col().map(|(line, col)| (line + 1, col + 1));
+        let mut message = error.to_string();
+
+        // The position is shown apart, with the column
+        if let Some((line, _)) = position {
+            let suffix = format!(" at line {line}");
+
+            if message.ends_with(&suffix) {
+                message.truncate(message.len() - suffix.len());
+            }
+        }
+
+        Self::Syntax {
+            path: path.to_path_buf(),
+            position,
+            message,
+        }
+    }
+
+    pub fn missing(path: &Path, kind: &'static str, name: &str) -> Self {
+        Self::Missing {
+            path: path.to_path_buf(),
+            kind,
+            name: name.to_string(),
+        }
+    }
+
+    pub fn invalid(path: &Path, message: impl Into<String>) -> Self {
+        Self::Invalid {
+            path: path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+
+    /// File the error was found in.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Io { path, .. }
+            | Self::Syntax { path, .. }
+            | Self::Image { path, .. }
+            | Self::TileSize { path, .. }
+            | Self::Missing { path, .. }
+            | Self::Invalid { path, .. } => path,
+        }
+    }
+}
+
+impl Display for PackError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let path = self.path().display();
+
+        match self {
+            Self::Io { error, .. } => write!(f, "{path}: {error}"),
+            Self::Syntax { position: Some((line, col)), message, .. } => write!(f, "{path}:{line}:{col}: {message}"),
+            Self::Syntax { position: None, message, .. } => write!(f, "{path}: {message}"),
+            Self::Image { error, .. } => write!(f, "{path}: {error}"),
+            Self::TileSize { width, height, .. } => write!(f, "{path}: tile is {width}x{height}, not {0}x{0}", super::CELL_SIZE),
+            Self::Missing { kind, name, .. } => write!(f, "{path}: no {kind} named `{name}`"),
+            Self::Invalid { message, .. } => write!(f, "{path}: {message}"),
+        }
+    }
+}
+
+impl Error for PackError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::Image { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
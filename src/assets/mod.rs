@@ -1,21 +1,31 @@
 use std::{
     ffi::{OsStr, OsString},
-    fs, iter,
-    path::{Path, PathBuf}, collections::BTreeMap, array,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf}, collections::{BTreeMap, HashMap, HashSet}, array,
 };
 
 use arrayvec::ArrayVec;
-use glam::{vec2, vec3, Vec2, Vec3};
+use glam::{ivec3, vec2, vec3, IVec3, Vec2, Vec3};
 use image::{imageops, RgbaImage};
 
 use crate::{
     graphics::Vertex,
-    types::{DirMap, SideMap},
+    light,
+    types::{DirMap, SideMap, DIRECTIONS},
+};
+
+pub use self::{
+    error::PackError,
+    raw::{Density, RenderLayer, Worldgen},
+    registry::{BlockId, BlockRegistry},
 };
 
 use self::raw::{Meshlet, Tilelet};
 
+mod error;
 mod raw;
+mod registry;
 
 pub const N_MIPS: usize = 5;
 
@@ -56,27 +66,239 @@ fn decompose_part<'m>(part: &'m Meshlet<'m>) -> ArrayVec<(Vec3, Vec3, Vec3, &'m
     }
 }
 
-const CELL_SIZE: u32 = 16;
+pub const CELL_SIZE: u32 = 16;
 
 pub type Quad = [Vertex; 4];
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct Block {
     pub culls: DirMap<bool>,
     pub mesh: SideMap<Box<[Quad]>>,
+
+    /// Whether the block is a single unit cuboid with one full tile per face,
+    /// so its faces can be merged with those of its neighbours.
+    pub cube: bool,
+
+    /// Name shown to players.
+    pub name: String,
+
+    /// Whether things collide with the block.
+    pub solid: bool,
+
+    /// Whether light goes through the block.
+    pub transparent: bool,
+
+    pub layer: RenderLayer,
+    pub emits: u8,
+
+    /// Effort taken to break the block, infinite if it cannot be broken.
+    pub hardness: f32,
+
+    /// Grip on top of the block, from 0 to 1.
+    pub friction: f32,
+
+    /// Properties the engine knows nothing about, as written in the pack.
+    pub custom: HashMap<String, toml::Value>,
+}
+
+/// Air, which nothing can see, touch or break.
+impl Default for Block {
+    fn default() -> Self {
+        Self {
+            culls: DirMap::default(),
+            mesh: SideMap::default(),
+            cube: false,
+            name: String::default(),
+            solid: false,
+            transparent: true,
+            layer: RenderLayer::default(),
+            emits: 0,
+            hardness: 0.,
+            friction: 0.,
+            custom: HashMap::default(),
+        }
+    }
+}
+
+impl Block {
+    /// Whether the block stops light.
+    pub fn opaque(&self) -> bool {
+        !self.transparent
+    }
+
+    pub fn translucent(&self) -> bool {
+        self.layer == RenderLayer::Translucent
+    }
+
+    /// Property not known to the engine, such as `flammable`.
+    pub fn property(&self, key: &str) -> Option<&toml::Value> {
+        self.custom.get(key)
+    }
+}
+
+/// A block stacked on top of the surface of some columns.
+#[derive(Debug, Clone)]
+pub struct Decoration {
+    pub block: BlockId,
+    pub chance: f64,
+    pub min_height: u32,
+    pub max_height: u32,
+}
+
+/// A structure some biome scatters over its surface.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    /// Index into the pack structures.
+    pub structure: usize,
+    pub chance: f64,
+}
+
+/// Kind of terrain picked for a column by its climate.
+#[derive(Debug, Clone)]
+pub struct Biome {
+    /// Climate where the biome is at its strongest, each from -1 to 1.
+    pub temperature: f64,
+    pub humidity: f64,
+
+    /// Topmost block of every column.
+    pub surface: BlockId,
+
+    /// Blocks between the surface and the stone below.
+    pub filler: BlockId,
+    pub filler_depth: i32,
+
+    /// Terrain height ranges from `height - variation` to `height + variation`.
+    pub height: f64,
+    pub variation: f64,
+
+    pub decorations: Box<[Decoration]>,
+    pub structures: Box<[Placement]>,
+}
+
+/// Blocks placed together, such as a tree.
+#[derive(Debug)]
+pub struct Structure {
+    /// Blocks relative to the top of the ground the structure stands on.
+    pub blocks: Box<[(IVec3, BlockId)]>,
+
+    /// Distance in blocks between spots where the structure may appear.
+    pub spacing: i32,
+
+    /// Furthest any block lies from the origin along x or y.
+    pub reach: i32,
+}
+
+/// A mineral buried in veins.
+#[derive(Debug)]
+pub struct Ore {
+    pub block: BlockId,
+    pub replaces: Box<[BlockId]>,
+    pub min_height: i32,
+    pub max_height: i32,
+    pub size: u32,
+
+    /// Average veins in every 32 by 32 by 32 volume.
+    pub frequency: f64,
 }
 
 #[derive(Debug)]
 pub struct Pack {
     pub atlases: [RgbaImage; N_MIPS],
-    pub blocks: Box<[(String, Block)]>,
+    pub blocks: BlockRegistry,
+    pub biomes: Box<[(String, Biome)]>,
+    pub structures: Box<[(String, Structure)]>,
+    pub ores: Box<[(String, Ore)]>,
+    pub worldgen: Worldgen,
 }
 
-fn open_tiles(root: &mut PathBuf) -> Option<([RgbaImage; N_MIPS], Vec<OsString>)> {
-    let mut tile_names = fs::read_dir(&root)
-        .ok()?
-        .map(|entry| Some(entry.ok()?.file_name()))
-        .try_collect::<Vec<_>>()?;
+impl Pack {
+    /// Looks a block up by name, such as `core:grass` or just `grass`.
+    pub fn find_block(&self, name: &str) -> Option<BlockId> {
+        self.blocks.id(name)
+    }
+}
+
+/// Looks something up by name in a sorted list, with or without its `.toml`
+/// extension.
+fn find<T>(items: &[(String, T)], name: &str) -> Option<usize> {
+    let search = |name: &str| items.binary_search_by(|(other, _)| other.as_str().cmp(name)).ok();
+    search(name).or_else(|| search(&format!("{name}.toml")))
+}
+
+/// Looks up something a file refers to by name, reporting it if missing.
+fn resolve<T>(items: &[(String, T)], kind: &'static str, name: &str, path: &Path, errors: &mut Vec<PackError>) -> Option<usize> {
+    let found = find(items, name);
+
+    if found.is_none() {
+        errors.push(PackError::missing(path, kind, name));
+    }
+
+    found
+}
+
+/// Like [`resolve`] for blocks, falling back to air so loading can go on.
+fn resolve_block(blocks: &BlockRegistry, name: &str, path: &Path, errors: &mut Vec<PackError>) -> BlockId {
+    blocks.id(name).unwrap_or_else(|| {
+        errors.push(PackError::missing(path, "block", name));
+        BlockId::AIR
+    })
+}
+
+/// Name, path and contents of every file in a directory, sorted by name.
+/// Only required directories are reported when missing.
+fn read_files(root: &Path, required: bool, errors: &mut Vec<PackError>) -> Vec<(String, PathBuf, Vec<u8>)> {
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(err) if !required && err.kind() == ErrorKind::NotFound => return Vec::new(),
+
+        Err(err) => {
+            errors.push(PackError::io(root, err));
+            return Vec::new();
+        }
+    };
+
+    let mut files = Vec::new();
+
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+
+            Err(err) => {
+                errors.push(PackError::io(root, err));
+                continue;
+            }
+        };
+
+        let path = entry.path();
+
+        match fs::read(&path) {
+            Ok(src) => files.push((entry.file_name().to_string_lossy().to_string(), path, src)),
+            Err(err) => errors.push(PackError::io(&path, err)),
+        }
+    }
+
+    files.sort_unstable_by(|(a, ..), (b, ..)| a.cmp(b));
+    files
+}
+
+fn open_tiles(root: &Path, errors: &mut Vec<PackError>) -> ([RgbaImage; N_MIPS], Vec<OsString>) {
+    let mut tile_names = match fs::read_dir(root) {
+        Ok(entries) => entries
+            .filter_map(|entry| match entry {
+                Ok(entry) => Some(entry.file_name()),
+
+                Err(err) => {
+                    errors.push(PackError::io(root, err));
+                    None
+                }
+            })
+            .collect::<Vec<_>>(),
+
+        Err(err) => {
+            errors.push(PackError::io(root, err));
+            Vec::new()
+        }
+    };
 
     tile_names.sort_unstable();
 
@@ -91,15 +313,28 @@ fn open_tiles(root: &mut PathBuf) -> Option<([RgbaImage; N_MIPS], Vec<OsString>)
     });
 
     for (idx, tile_name) in tile_names.iter().enumerate() {
-        root.push(tile_name);
-
+        let path = root.join(tile_name);
         let idx = idx as u32;
-        let tile = image::open(&root).ok()?.to_rgba8();
 
-        debug_assert!(
-            [tile.width(), tile.height()] == [CELL_SIZE, CELL_SIZE],
-            "incorrect tile size",
-        );
+        // Broken tiles are left blank so the others keep their cells
+        let tile = match image::open(&path) {
+            Ok(tile) => tile.to_rgba8(),
+
+            Err(error) => {
+                errors.push(PackError::Image { path, error });
+                continue;
+            }
+        };
+
+        if [tile.width(), tile.height()] != [CELL_SIZE, CELL_SIZE] {
+            errors.push(PackError::TileSize {
+                path,
+                width: tile.width(),
+                height: tile.height(),
+            });
+
+            continue;
+        }
 
         for mip_lvl in 0..N_MIPS {
             let x = (CELL_SIZE >> mip_lvl as u32) * (idx % width_cells);
@@ -112,101 +347,579 @@ fn open_tiles(root: &mut PathBuf) -> Option<([RgbaImage; N_MIPS], Vec<OsString>)
                 imageops::replace(&mut atlases[0], &tile, x as _, y as _);
             }
         }
-
-        root.pop();
     }
 
-    Some((atlases, tile_names))
+    (atlases, tile_names)
 }
 
-fn open_blocks(root: &mut PathBuf, tile_names: &[OsString]) -> Option<Vec<(String, Block)>> {
-    let width_cells = {
-        let num_cells = tile_names.len().next_power_of_two();
-        num_cells.isqrt() as u32
+fn is_cube(parts: &[Meshlet]) -> bool {
+    let [Meshlet::Cuboid { xyz0, xyz1, faces }] = parts else {
+        return false;
     };
 
-    let iter = fs::read_dir(root).ok()?.map(|entry| {
-        let entry = entry.ok()?;
-        let name = entry.file_name().to_string_lossy().to_string();
-        let path = entry.path();
-        let src = fs::read(path).ok()?;
-        let mut mesh = SideMap::<Vec<_>>::default();
-        let raw::Block { culls, parts } = toml::from_slice(&src).unwrap();
-
-        for (xyz0, xyz1, xyz2, face) in parts.iter().flat_map(decompose_part) {
-            let Tilelet {
-                tile,
-                mut uv0,
-                mut uv1,
-                cull,
-            } = *face;
-
-            let tile_name = OsStr::new(tile);
-
-            let idx = tile_names
-                .binary_search_by_key(&tile_name, AsRef::as_ref)
-                .ok()? as u32;
-
-            let s = idx % width_cells;
-            let t = idx / width_cells;
-
-            for uv in [&mut uv0, &mut uv1] {
-                *uv += vec2(s as _, t as _);
-                *uv /= Vec2::splat(width_cells as _);
+    *xyz0 == Vec3::ZERO
+        && *xyz1 == Vec3::ONE
+        && DIRECTIONS.into_iter().all(|dir| {
+            let face = &faces[dir];
+            face.cull == Some(dir) && (face.uv0 - face.uv1).abs() == Vec2::ONE
+        })
+}
+
+/// Name shown for blocks without one, such as `Coal ore` for `coal_ore.toml`.
+fn display_name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy().replace('_', " ");
+    let mut chars = stem.chars();
+
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => stem,
+    }
+}
+
+/// Settles the properties of a block, reporting those out of range and
+/// replacing them, along with any left out, with their defaults.
+fn open_properties(raw: raw::Properties, culls: DirMap<bool>, path: &Path, errors: &mut Vec<PackError>) -> Block {
+    let mut emits = raw.light.unwrap_or(0);
+    let mut hardness = raw.hardness.unwrap_or(1.);
+    let mut friction = raw.friction.unwrap_or(0.6);
+
+    if emits > light::MAX_LIGHT {
+        errors.push(PackError::invalid(path, format!("light must be at most {}, not {emits}", light::MAX_LIGHT)));
+        emits = 0;
+    }
+
+    if !(hardness >= 0.) {
+        errors.push(PackError::invalid(path, format!("hardness must be at least 0, not {hardness}")));
+        hardness = 1.;
+    }
+
+    if !(0. ..=1.).contains(&friction) {
+        errors.push(PackError::invalid(path, format!("friction must be between 0 and 1, not {friction}")));
+        friction = 0.6;
+    }
+
+    // Light only stops at blocks that cull every neighbour, unless told otherwise
+    let transparent = raw.transparent.unwrap_or_else(|| {
+        let DirMap { west, east, south, north, down, up } = culls;
+        !(west && east && south && north && down && up)
+    });
+
+    Block {
+        culls,
+        name: raw.name.unwrap_or_else(|| display_name(path)),
+        solid: raw.solid.unwrap_or(true),
+        transparent,
+        layer: raw.layer.unwrap_or_default(),
+        emits,
+        hardness,
+        friction,
+        custom: raw.custom,
+        ..Block::default()
+    }
+}
+
+/// Name of a model as written in `parent` keys, with or without its `.toml`
+/// extension.
+fn model_name(name: &str) -> &str {
+    name.strip_suffix(".toml").unwrap_or(name)
+}
+
+/// Reads every model, a pack without any being allowed, and fills each one
+/// in from its own parents. Models inheriting in a loop or from one missing
+/// keep whatever they got so far.
+fn open_models<'m>(files: &'m [(String, PathBuf, Vec<u8>)], errors: &mut Vec<PackError>) -> HashMap<&'m str, raw::Block<'m>> {
+    let mut models = Vec::new();
+
+    for (name, path, src) in files {
+        match toml::from_slice::<raw::Block>(src) {
+            Ok(model) => models.push((model_name(name), path, model)),
+            Err(err) => errors.push(PackError::syntax(path, err)),
+        }
+    }
+
+    let lookup = |name: &str| models.iter().find(|(other, ..)| *other == name).map(|(.., model)| model);
+    let mut resolved = HashMap::new();
+
+    for (name, path, model) in models.iter() {
+        let mut model = model.clone();
+        let mut chain = vec![*name];
+
+        // Problems further up the chain are reported by the models having them
+        while let Some(parent) = model.parent.take().map(model_name) {
+            if parent == *name {
+                chain.push(parent);
+                errors.push(PackError::invalid(path, format!("models inherit in a loop: {}", chain.join(" -> "))));
+                break;
+            }
+
+            if chain.contains(&parent) {
+                break;
             }
 
-            let xyz3 = xyz2 - (xyz1 - xyz0);
-            let u0v0 = uv0;
-            let u0v1 = vec2(uv0.x, uv1.y);
-            let u1v0 = vec2(uv1.x, uv0.y);
-            let u1v1 = uv1;
+            let Some(parent_model) = lookup(parent) else {
+                if chain.len() == 1 {
+                    errors.push(PackError::missing(path, "model", parent));
+                }
+
+                break;
+            };
+
+            chain.push(parent);
+            model.inherit(parent_model);
+        }
+
+        resolved.insert(*name, model);
+    }
+
+    resolved
+}
+
+/// Tile a face ends up with, following `#name` variables through `textures`,
+/// which may name other variables. Failures come along with the variable at
+/// fault, or the first one of a loop, so faces sharing it report it once.
+fn substitute<'t>(tile: &'t str, textures: &HashMap<&'t str, &'t str>, path: &Path) -> Result<&'t str, (&'t str, PackError)> {
+    let mut current = tile;
+    let mut seen = Vec::new();
+
+    while let Some(name) = current.strip_prefix('#') {
+        if let Some(idx) = seen.iter().position(|other| *other == name) {
+            let mut cycle = seen.split_off(idx);
+            let first = *cycle.iter().min().unwrap();
+            let start = cycle.iter().position(|other| *other == first).unwrap();
 
-            let normal = (xyz1 - xyz0).cross(xyz3 - xyz0).normalize();
-            let shadow = 1. - 0.2 * normal.x.abs() - 0.4 * normal.y.abs();
-            let light = 15;
+            cycle.rotate_left(start);
+            cycle.push(first);
 
-            #[rustfmt::skip]
-            mesh[cull].push([
-                Vertex { xyz: xyz0, uv: u1v0, shadow, light },
-                Vertex { xyz: xyz1, uv: u0v0, shadow, light },
-                Vertex { xyz: xyz2, uv: u0v1, shadow, light },
-                Vertex { xyz: xyz3, uv: u1v1, shadow, light },
-            ]);
+            let message = format!("textures refer to each other in a loop: {}", cycle.join(" -> "));
+            return Err((first, PackError::invalid(path, message)));
         }
 
-        let block = Block {
-            culls,
-            mesh: mesh.map(Vec::into_boxed_slice),
+        seen.push(name);
+
+        current = match textures.get(name) {
+            Some(tile) => tile,
+            None => return Err((name, PackError::missing(path, "texture", name))),
         };
+    }
 
-        Some((name, block))
-    });
+    Ok(current)
+}
 
-    let air = Block {
-        culls: DirMap::default(),
-        mesh: SideMap::default(),
+fn open_block(src: &[u8], path: &Path, tile_names: &[OsString], models: &HashMap<&str, raw::Block>, errors: &mut Vec<PackError>) -> Block {
+    let mut raw: raw::Block = match toml::from_slice(src) {
+        Ok(raw) => raw,
+
+        Err(err) => {
+            errors.push(PackError::syntax(path, err));
+            return Block::default();
+        }
     };
 
-    let air = Block::default();
-    iter::once(Some((String::from("air"), air))).chain(iter).try_collect::<Vec<_>>()
+    let parent = raw.parent.take().map(model_name);
+
+    if let Some(parent) = parent {
+        match models.get(parent) {
+            Some(model) => raw.inherit(model),
+            None => errors.push(PackError::missing(path, "model", parent)),
+        }
+    }
+
+    let raw::Block { textures, culls, properties, parts, .. } = raw;
+
+    let Some(parts) = parts else {
+        // Blocks of a missing model are reported already
+        if parent.is_none() {
+            errors.push(PackError::invalid(path, "no parts, nor a model to take them from"));
+        }
+
+        return Block::default();
+    };
+
+    let culls = culls.unwrap_or_default();
+
+    let mut mesh = SideMap::<Vec<_>>::default();
+    let mut missing = HashSet::new();
+
+    for (xyz0, xyz1, xyz2, face) in parts.iter().flat_map(decompose_part) {
+        let Tilelet {
+            tile,
+            uv0,
+            uv1,
+            cull,
+        } = *face;
+
+        // Faces often share tiles, report each one once
+        let tile = match substitute(tile, &textures, path) {
+            Ok(tile) => tile,
+
+            Err((name, err)) => {
+                if missing.insert(format!("#{name}")) {
+                    errors.push(err);
+                }
+
+                continue;
+            }
+        };
+
+        let tile_name = OsStr::new(tile);
+
+        let Ok(tile) = tile_names.binary_search_by_key(&tile_name, AsRef::as_ref) else {
+            if missing.insert(tile.to_string()) {
+                errors.push(PackError::missing(path, "tile", tile));
+            }
+
+            continue;
+        };
+
+        let tile = tile as u32;
+        let xyz3 = xyz2 - (xyz1 - xyz0);
+        let u0v0 = uv0;
+        let u0v1 = vec2(uv0.x, uv1.y);
+        let u1v0 = vec2(uv1.x, uv0.y);
+        let u1v1 = uv1;
+
+        let normal = (xyz1 - xyz0).cross(xyz3 - xyz0).normalize();
+        let shadow = 1. - 0.2 * normal.x.abs() - 0.4 * normal.y.abs();
+        let light = 15;
+
+        #[rustfmt::skip]
+        mesh[cull].push([
+            Vertex { xyz: xyz0, uv: u1v0, shadow, light, tile },
+            Vertex { xyz: xyz1, uv: u0v0, shadow, light, tile },
+            Vertex { xyz: xyz2, uv: u0v1, shadow, light, tile },
+            Vertex { xyz: xyz3, uv: u1v1, shadow, light, tile },
+        ]);
+    }
+
+    Block {
+        mesh: mesh.map(Vec::into_boxed_slice),
+        cube: is_cube(&parts),
+        ..open_properties(properties, culls, path, errors)
+    }
 }
 
-pub fn open(path: impl AsRef<Path>) -> Option<Pack> {
-    let mut path = path.as_ref().to_path_buf();
+/// Reads every block, naming it after its file in the `core` namespace.
+/// Broken ones are kept as air, so whatever refers to them does not report
+/// them again.
+fn open_blocks(root: &Path, tile_names: &[OsString], models: &HashMap<&str, raw::Block>, errors: &mut Vec<PackError>) -> BlockRegistry {
+    let blocks = read_files(root, true, errors)
+        .into_iter()
+        .map(|(name, path, src)| (name, open_block(&src, &path, tile_names, models, errors)))
+        .collect::<Vec<_>>();
+
+    BlockRegistry::new(blocks)
+}
+
+fn open_structure(src: &[u8], path: &Path, blocks: &BlockRegistry, errors: &mut Vec<PackError>) -> Structure {
+    let raw: raw::Structure = match toml::from_slice(src) {
+        Ok(raw) => raw,
+
+        Err(err) => {
+            errors.push(PackError::syntax(path, err));
+
+            return Structure {
+                blocks: Box::new([]),
+                spacing: 1,
+                reach: 0,
+            };
+        }
+    };
 
-    path.push("tiles");
-    let (atlases, tile_names) = open_tiles(&mut path)?;
+    let [x0, y0] = raw.origin;
+    let mut palette = HashMap::new();
 
-    path.pop();
-    path.push("blocks");
-    let blocks = open_blocks(&mut path, &tile_names)?;
+    for (key, block) in raw.palette.iter() {
+        let mut chars = key.chars();
+
+        match (chars.next(), chars.next()) {
+            (Some(key), None) => {
+                palette.insert(key, resolve_block(blocks, block, path, errors));
+            }
+
+            _ => errors.push(PackError::invalid(path, format!("palette key `{key}` is not a single character"))),
+        }
+    }
+
+    let mut blocks = Vec::new();
+
+    for (k, layer) in raw.layers.iter().enumerate() {
+        for (j, row) in layer.iter().enumerate() {
+            // Keys missing from the palette leave the terrain alone
+            for (i, key) in row.chars().enumerate() {
+                if let Some(block) = palette.get(&key) {
+                    blocks.push((ivec3(i as i32 - x0, j as i32 - y0, k as i32 + 1), *block));
+                }
+            }
+        }
+    }
+
+    let reach = blocks
+        .iter()
+        .map(|(offset, _)| offset.x.abs().max(offset.y.abs()))
+        .max()
+        .unwrap_or(0);
+
+    Structure {
+        blocks: blocks.into_boxed_slice(),
+        spacing: raw.spacing.max(1),
+        reach,
+    }
+}
+
+/// Reads every structure, a pack without any being allowed.
+fn open_structures(root: &Path, blocks: &BlockRegistry, errors: &mut Vec<PackError>) -> Vec<(String, Structure)> {
+    read_files(root, false, errors)
+        .into_iter()
+        .map(|(name, path, src)| (name, open_structure(&src, &path, blocks, errors)))
+        .collect()
+}
+
+fn open_biome(src: &[u8], path: &Path, blocks: &BlockRegistry, structures: &[(String, Structure)], errors: &mut Vec<PackError>) -> Option<Biome> {
+    let raw: raw::Biome = match toml::from_slice(src) {
+        Ok(raw) => raw,
+
+        Err(err) => {
+            errors.push(PackError::syntax(path, err));
+            return None;
+        }
+    };
+
+    let decorations = raw
+        .decorations
+        .iter()
+        .map(|decoration| {
+            let [min_height, max_height] = decoration.height;
+
+            Decoration {
+                block: resolve_block(blocks, decoration.block, path, errors),
+                chance: decoration.chance,
+                min_height,
+                max_height: max_height.max(min_height),
+            }
+        })
+        .collect();
+
+    let placements = raw
+        .structures
+        .iter()
+        .filter_map(|placement| {
+            Some(Placement {
+                structure: resolve(structures, "structure", placement.structure, path, errors)?,
+                chance: placement.chance,
+            })
+        })
+        .collect();
+
+    Some(Biome {
+        temperature: raw.temperature,
+        humidity: raw.humidity,
+        surface: resolve_block(blocks, raw.surface, path, errors),
+        filler: resolve_block(blocks, raw.filler, path, errors),
+        filler_depth: raw.filler_depth,
+        height: raw.height,
+        variation: raw.variation,
+        decorations,
+        structures: placements,
+    })
+}
+
+/// Reads every biome, a pack without any being allowed.
+fn open_biomes(root: &Path, blocks: &BlockRegistry, structures: &[(String, Structure)], errors: &mut Vec<PackError>) -> Vec<(String, Biome)> {
+    read_files(root, false, errors)
+        .into_iter()
+        .filter_map(|(name, path, src)| Some((name, open_biome(&src, &path, blocks, structures, errors)?)))
+        .collect()
+}
+
+fn open_ore(src: &[u8], path: &Path, blocks: &BlockRegistry, errors: &mut Vec<PackError>) -> Option<Ore> {
+    let raw: raw::Ore = match toml::from_slice(src) {
+        Ok(raw) => raw,
+
+        Err(err) => {
+            errors.push(PackError::syntax(path, err));
+            return None;
+        }
+    };
+
+    let [min_height, max_height] = raw.height;
+
+    let replaces = raw
+        .replaces
+        .iter()
+        .map(|block| resolve_block(blocks, block, path, errors))
+        .collect();
+
+    Some(Ore {
+        block: resolve_block(blocks, raw.block, path, errors),
+        replaces,
+        min_height,
+        max_height: max_height.max(min_height),
+        size: raw.size,
+        frequency: raw.frequency,
+    })
+}
+
+/// Reads every ore, a pack without any being allowed.
+fn open_ores(root: &Path, blocks: &BlockRegistry, errors: &mut Vec<PackError>) -> Vec<(String, Ore)> {
+    read_files(root, false, errors)
+        .into_iter()
+        .filter_map(|(name, path, src)| Some((name, open_ore(&src, &path, blocks, errors)?)))
+        .collect()
+}
+
+/// Reads the generation settings, all of them optional.
+fn open_worldgen(path: &Path, errors: &mut Vec<PackError>) -> Worldgen {
+    let src = match fs::read(path) {
+        Ok(src) => src,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Worldgen::default(),
+
+        Err(err) => {
+            errors.push(PackError::io(path, err));
+            return Worldgen::default();
+        }
+    };
+
+    toml::from_slice(&src).unwrap_or_else(|err| {
+        errors.push(PackError::syntax(path, err));
+        Worldgen::default()
+    })
+}
+
+/// Loads the pack at `path`, reporting every problem found in its files
+/// rather than just the first one.
+pub fn open(path: impl AsRef<Path>) -> Result<Pack, Vec<PackError>> {
+    let path = path.as_ref();
+    let mut errors = Vec::new();
+
+    let (atlases, tile_names) = open_tiles(&path.join("tiles"), &mut errors);
+    let model_files = read_files(&path.join("models"), false, &mut errors);
+    let models = open_models(&model_files, &mut errors);
+    let blocks = open_blocks(&path.join("blocks"), &tile_names, &models, &mut errors);
+    let structures = open_structures(&path.join("structures"), &blocks, &mut errors);
+    let biomes = open_biomes(&path.join("biomes"), &blocks, &structures, &mut errors);
+    let ores = open_ores(&path.join("ores"), &blocks, &mut errors);
+    let worldgen = open_worldgen(&path.join("worldgen.toml"), &mut errors);
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
 
     for (idx, atlas) in atlases.iter().enumerate() {
         atlas.save(format!("atlas_{}.png", idx));
     }
 
-    Some(Pack {
+    Ok(Pack {
         atlases,
-        blocks: blocks.into_boxed_slice(),
+        blocks,
+        biomes: biomes.into_boxed_slice(),
+        structures: structures.into_boxed_slice(),
+        ores: ores.into_boxed_slice(),
+        worldgen,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(models: &[(&str, &str)]) -> Vec<(String, PathBuf, Vec<u8>)> {
+        models
+            .iter()
+            .map(|(name, src)| (format!("{name}.toml"), PathBuf::from(format!("models/{name}.toml")), src.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn messages(errors: &[PackError]) -> Vec<String> {
+        errors.iter().map(PackError::to_string).collect()
+    }
+
+    #[test]
+    fn substitution_follows_variables() {
+        let textures = HashMap::from([("all", "stone.png"), ("side", "#all"), ("end", "#side")]);
+        let path = Path::new("blocks/stone.toml");
+
+        assert_eq!(substitute("dirt.png", &textures, path).unwrap(), "dirt.png");
+        assert_eq!(substitute("#end", &textures, path).unwrap(), "stone.png");
+
+        let (name, err) = substitute("#end", &HashMap::from([("end", "#top")]), path).unwrap_err();
+        assert_eq!((name, err.to_string()), ("top", "blocks/stone.toml: no texture named `top`".to_string()));
+    }
+
+    #[test]
+    fn substitution_loops_start_at_the_same_variable() {
+        let textures = HashMap::from([("a", "#b"), ("b", "#c"), ("c", "#a"), ("d", "#b")]);
+        let path = Path::new("blocks/stone.toml");
+
+        // However a loop is entered, it is reported once, from its first name
+        for tile in ["#a", "#b", "#c", "#d"] {
+            let (name, err) = substitute(tile, &textures, path).unwrap_err();
+            assert_eq!(name, "a");
+            assert_eq!(err.to_string(), "blocks/stone.toml: textures refer to each other in a loop: a -> b -> c -> a");
+        }
+
+        let (name, _) = substitute("#self", &HashMap::from([("self", "#self")]), path).unwrap_err();
+        assert_eq!(name, "self");
+    }
+
+    #[test]
+    fn models_inherit_along_chains() {
+        let files = files(&[
+            ("base", "[properties]\nsolid = false\nlight = 4\n[properties.custom]\nplant = true\n[textures]\nall = \"#cross\"\n"),
+            ("middle", "parent = \"base.toml\"\n[properties]\nlight = 7\n[textures]\ncross = \"grass.png\"\n"),
+            ("top", "parent = \"middle\"\n[properties]\nhardness = 0.5\n"),
+        ]);
+
+        let mut errors = Vec::new();
+        let models = open_models(&files, &mut errors);
+        let top = &models["top"];
+
+        assert!(errors.is_empty());
+        assert!(top.parent.is_none());
+        assert_eq!((top.properties.solid, top.properties.light, top.properties.hardness), (Some(false), Some(7), Some(0.5)));
+        assert_eq!(top.properties.custom.get("plant").and_then(toml::Value::as_bool), Some(true));
+        assert_eq!(substitute("#all", &top.textures, Path::new("top.toml")).unwrap(), "grass.png");
+    }
+
+    #[test]
+    fn properties_out_of_range_fall_back_to_defaults() {
+        let raw = toml::from_str::<raw::Properties>("light = 99\nhardness = -1\nfriction = 2\n[custom]\nflammable = true\n").unwrap();
+        let path = Path::new("blocks/odd_stone.toml");
+
+        let mut errors = Vec::new();
+        let block = open_properties(raw, DirMap::default(), path, &mut errors);
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!((block.emits, block.hardness, block.friction), (0, 1., 0.6));
+        assert_eq!((block.name.as_str(), block.solid), ("Odd stone", true));
+        assert_eq!(block.property("flammable").and_then(toml::Value::as_bool), Some(true));
+        assert!(block.property("edible").is_none());
+    }
+
+    #[test]
+    fn model_problems_are_reported_where_they_are() {
+        let files = files(&[
+            ("a", "parent = \"b\"\n"),
+            ("b", "parent = \"a\"\n"),
+            ("c", "parent = \"a\"\n"),
+            ("d", "parent = \"nope\"\n"),
+            ("e", "parent = \"d\"\n"),
+            ("f", "parent = \"f\"\n"),
+        ]);
+
+        let mut errors = Vec::new();
+        let models = open_models(&files, &mut errors);
+
+        // Models inheriting from broken ones, such as c and e, are not blamed
+        assert_eq!(
+            messages(&errors),
+            [
+                "models/a.toml: models inherit in a loop: a -> b -> a",
+                "models/b.toml: models inherit in a loop: b -> a -> b",
+                "models/d.toml: no model named `nope`",
+                "models/f.toml: models inherit in a loop: f -> f",
+            ]
+        );
+
+        assert_eq!(models.len(), 6);
+    }
+}
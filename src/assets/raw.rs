@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use glam::{Vec2, Vec3};
 use serde::Deserialize;
 
@@ -15,7 +17,7 @@ fn vec3_one() -> Vec3 {
     Vec3::ONE
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Copy, Deserialize)]
 pub(super) struct Tilelet<'t> {
     #[serde(borrow)]
     pub tile: &'t str,
@@ -30,7 +32,7 @@ pub(super) struct Tilelet<'t> {
     pub cull: Option<Direction>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[serde(tag = "type")]
 pub(super) enum Meshlet<'m> {
@@ -57,11 +59,309 @@ pub(super) enum Meshlet<'m> {
     },
 }
 
-#[derive(Debug, Deserialize)]
+/// Pass a block is drawn in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderLayer {
+    /// Drawn first, with fully transparent texels left out.
+    #[default]
+    Opaque,
+
+    /// Blended over whatever lies behind it, like water. Faces between two
+    /// such blocks of the same kind are never drawn.
+    Translucent,
+}
+
+/// Behaviour of a block, all of it optional. Unknown keys are rejected so
+/// typos do not go unnoticed, anything else goes under `custom`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(super) struct Properties {
+    /// Name shown to players, made up from the file name if missing.
+    pub name: Option<String>,
+
+    /// Whether things collide with the block, by default they do.
+    pub solid: Option<bool>,
+
+    /// Whether light goes through the block, by default unless it culls
+    /// every neighbour.
+    pub transparent: Option<bool>,
+
+    /// Block light emitted, from 0 to 15.
+    pub light: Option<u8>,
+
+    /// Effort taken to break the block, `inf` if it cannot be broken.
+    pub hardness: Option<f32>,
+
+    /// Grip on top of the block, from 0 to 1.
+    pub friction: Option<f32>,
+
+    pub layer: Option<RenderLayer>,
+
+    /// Anything else a pack wants to note about a block, for gameplay to
+    /// read from data instead of hardcoding names.
+    #[serde(default)]
+    pub custom: HashMap<String, toml::Value>,
+}
+
+impl Properties {
+    /// Fills in whatever is left out from those of the parent, key by key.
+    pub fn inherit(&mut self, parent: &Properties) {
+        self.name = self.name.take().or_else(|| parent.name.clone());
+        self.solid = self.solid.or(parent.solid);
+        self.transparent = self.transparent.or(parent.transparent);
+        self.light = self.light.or(parent.light);
+        self.hardness = self.hardness.or(parent.hardness);
+        self.friction = self.friction.or(parent.friction);
+        self.layer = self.layer.or(parent.layer);
+
+        for (key, value) in parent.custom.iter() {
+            self.custom.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+}
+
+/// A block or a model blocks are based on. Whatever is left out comes from
+/// the parent model, if any, `properties` and `textures` key by key and
+/// other tables whole.
+#[derive(Debug, Clone, Deserialize)]
 pub(super) struct Block<'b> {
+    #[serde(borrow)]
+    pub parent: Option<&'b str>,
+
+    /// Tiles standing for the `#name` variables of the parts, which may in
+    /// turn name other variables.
     #[serde(default)]
-    pub culls: DirMap<bool>,
+    #[serde(borrow)]
+    pub textures: HashMap<&'b str, &'b str>,
+
+    pub culls: Option<DirMap<bool>>,
+
+    #[serde(default)]
+    pub properties: Properties,
 
     #[serde(borrow)]
-    pub parts: Box<[Meshlet<'b>]>,
+    pub parts: Option<Box<[Meshlet<'b>]>>,
+}
+
+impl<'b> Block<'b> {
+    /// Fills in whatever is left out from `model`, which becomes the parent.
+    pub fn inherit(&mut self, model: &Block<'b>) {
+        self.parent = model.parent;
+
+        for (name, tile) in model.textures.iter() {
+            self.textures.entry(name).or_insert(tile);
+        }
+
+        self.culls = self.culls.take().or_else(|| model.culls.clone());
+        self.properties.inherit(&model.properties);
+        self.parts = self.parts.take().or_else(|| model.parts.clone());
+    }
+}
+
+fn filler_depth() -> i32 {
+    4
+}
+
+fn one() -> [u32; 2] {
+    [1, 1]
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Decoration<'d> {
+    #[serde(borrow)]
+    pub block: &'d str,
+
+    /// Odds of a column being decorated, from 0 to 1.
+    pub chance: f64,
+
+    /// Smallest and largest number of blocks stacked.
+    #[serde(default = "one")]
+    pub height: [u32; 2],
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Placement<'p> {
+    #[serde(borrow)]
+    pub structure: &'p str,
+
+    /// Odds of the structure appearing at each candidate spot, from 0 to 1.
+    pub chance: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Biome<'b> {
+    pub temperature: f64,
+    pub humidity: f64,
+
+    #[serde(borrow)]
+    pub surface: &'b str,
+
+    #[serde(borrow)]
+    pub filler: &'b str,
+
+    #[serde(default = "filler_depth")]
+    pub filler_depth: i32,
+
+    pub height: f64,
+    pub variation: f64,
+
+    #[serde(default)]
+    #[serde(borrow)]
+    pub decorations: Box<[Decoration<'b>]>,
+
+    #[serde(default)]
+    #[serde(borrow)]
+    pub structures: Box<[Placement<'b>]>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Structure<'s> {
+    /// Column of the bottom layer standing on the ground, as column and row.
+    pub origin: [i32; 2],
+
+    /// Distance in blocks between candidate spots.
+    pub spacing: i32,
+
+    /// Layers from the bottom up, each one rows of single character keys.
+    #[serde(borrow)]
+    pub layers: Box<[Box<[&'s str]>]>,
+
+    #[serde(borrow)]
+    pub palette: HashMap<&'s str, &'s str>,
+}
+
+fn replaces() -> Box<[&'static str]> {
+    Box::new(["stone", "cobblestone"])
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct Ore<'o> {
+    #[serde(borrow)]
+    pub block: &'o str,
+
+    /// Lowest and highest blocks of a vein.
+    pub height: [i32; 2],
+
+    /// Blocks in a vein, although some may overlap.
+    pub size: u32,
+
+    /// Average veins in every 32 by 32 by 32 volume.
+    pub frequency: f64,
+
+    /// Blocks a vein may replace, anything else is left alone.
+    #[serde(default = "replaces")]
+    #[serde(borrow)]
+    pub replaces: Box<[&'o str]>,
+}
+
+/// Bends the height map with 3D noise, giving overhangs and cliffs.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Density {
+    /// Largest shift of the surface in blocks, zero for a plain height map.
+    pub strength: f64,
+    pub scale: f64,
+}
+
+impl Default for Density {
+    fn default() -> Self {
+        Self {
+            strength: 0.,
+            scale: 24.,
+        }
+    }
+}
+
+/// Caves dug out of the terrain by 3D noise: chambers where a noise field
+/// peaks and tunnels where two fields cross zero together.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Caves {
+    pub chamber_scale: f64,
+    pub chamber_threshold: f64,
+    pub tunnel_scale: f64,
+    pub tunnel_width: f64,
+
+    /// Blocks kept between chambers and the surface. Tunnels may open up.
+    pub cover: i32,
+
+    /// Solid layers kept above the bedrock floor.
+    pub floor_margin: i32,
+}
+
+impl Default for Caves {
+    fn default() -> Self {
+        Self {
+            chamber_scale: 40.,
+            chamber_threshold: 0.6,
+            tunnel_scale: 56.,
+            tunnel_width: 0.06,
+            cover: 8,
+            floor_margin: 3,
+        }
+    }
+}
+
+/// Heights blocks may exist at. Nothing is generated, loaded or placed
+/// outside of them.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Height {
+    /// Lowest layer, made of bedrock by generators with a floor.
+    pub bottom: i32,
+
+    /// First layer above the highest one allowed.
+    pub top: i32,
+}
+
+impl Default for Height {
+    fn default() -> Self {
+        Self {
+            bottom: -128,
+            top: 256,
+        }
+    }
+}
+
+fn water() -> String {
+    String::from("water")
+}
+
+fn sand() -> String {
+    String::from("sand")
+}
+
+fn beach_height() -> i32 {
+    2
+}
+
+/// Water filling every dip in the terrain up to some height.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Sea {
+    /// Topmost layer of water.
+    pub level: i32,
+
+    #[serde(default = "water")]
+    pub block: String,
+
+    /// Covers shores and shallow sea floors.
+    #[serde(default = "sand")]
+    pub beach: String,
+
+    /// How far above the sea beaches reach.
+    #[serde(default = "beach_height")]
+    pub beach_height: i32,
+}
+
+/// Generation settings shared by every biome, read from `worldgen.toml`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Worldgen {
+    pub height: Height,
+    pub density: Density,
+    pub caves: Caves,
+
+    /// Worlds without a sea are dry all over.
+    pub sea: Option<Sea>,
 }
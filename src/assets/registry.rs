@@ -0,0 +1,97 @@
+use std::{collections::HashMap, fmt, ops::Index};
+
+use super::Block;
+
+/// Namespace of the blocks every pack file may refer to without one.
+pub const CORE: &str = "core";
+
+/// Identifies a block type for as long as a pack is loaded. Saved worlds
+/// translate them through the block names, as other packs number them
+/// differently.
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub u16);
+
+impl BlockId {
+    pub const AIR: Self = Self(0);
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Namespaced name of a block as written in pack files, where the `core`
+/// namespace and the `.toml` extension of the block file may be left out.
+pub fn canonical(name: &str) -> String {
+    let name = name.strip_suffix(".toml").unwrap_or(name);
+
+    match name.contains(':') {
+        true => name.to_string(),
+        false => format!("{CORE}:{name}"),
+    }
+}
+
+/// Every block of a pack by namespaced name, such as `core:grass`, and by
+/// ID. Air is always there, with [`BlockId::AIR`].
+#[derive(Debug)]
+pub struct BlockRegistry {
+    blocks: Box<[(String, Block)]>,
+    ids: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+    /// Numbers blocks in the order of their names, after air.
+    pub fn new(blocks: impl IntoIterator<Item = (String, Block)>) -> Self {
+        let mut blocks = blocks
+            .into_iter()
+            .map(|(name, block)| (canonical(&name), block))
+            .filter(|(name, _)| *name != format!("{CORE}:air"))
+            .collect::<Vec<_>>();
+
+        blocks.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        blocks.insert(0, (format!("{CORE}:air"), Block::default()));
+
+        let ids = blocks
+            .iter()
+            .enumerate()
+            .map(|(idx, (name, _))| (name.clone(), BlockId(idx as u16)))
+            .collect();
+
+        Self {
+            blocks: blocks.into_boxed_slice(),
+            ids,
+        }
+    }
+
+    /// Looks a block up by name, written as in pack files.
+    pub fn id(&self, name: &str) -> Option<BlockId> {
+        self.ids.get(&canonical(name)).copied()
+    }
+
+    /// Namespaced name of a block.
+    pub fn name(&self, id: BlockId) -> &str {
+        &self.blocks[id.0 as usize].0
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Every block along with its ID and name, air first.
+    pub fn iter(&self) -> impl Iterator<Item = (BlockId, &str, &Block)> {
+        self.blocks
+            .iter()
+            .enumerate()
+            .map(|(idx, (name, block))| (BlockId(idx as u16), name.as_str(), block))
+    }
+}
+
+impl Index<BlockId> for BlockRegistry {
+    type Output = Block;
+
+    fn index(&self, id: BlockId) -> &Self::Output {
+        &self.blocks[id.0 as usize].1
+    }
+}
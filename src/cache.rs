@@ -1,5 +1,103 @@
-pub struct Cache<const N: usize = 16> {
-    entries: [[[Arc<RwLock<Chunk>>; 16]; 16]; 16],
+use std::{
+    iter,
+    sync::{Arc, RwLock},
+};
+
+use glam::IVec3;
+
+use crate::chunk::Chunk;
+
+/// Chunks within an `N`×`N`×`N` window around a center, stored in a toroidal
+/// array so that moving the center never shifts the chunks already cached.
+/// Chunks are shared behind locks so worker threads can read them.
+///
+/// The window only sizes the array of slots, the chunks actually held being
+/// up to whoever loads them. It must be wider than
+/// 2 × (streaming distance + 1) chunks, so that the chunks streamed in and the
+/// neighbours meshing looks at all fit: at least 22 with the distance of 10
+/// set in `main.rs`.
+pub struct Cache<const N: usize = 32> {
+    center: IVec3,
+    entries: Box<[Option<(IVec3, Arc<RwLock<Chunk>>)>]>,
+}
+
+impl<const N: usize> Default for Cache<N> {
+    fn default() -> Self {
+        Self {
+            center: IVec3::ZERO,
+            entries: iter::repeat_with(|| None).take(N * N * N).collect(),
+        }
+    }
 }
 
-impl Cache {}
+impl<const N: usize> Cache<N> {
+    pub fn center(&self) -> IVec3 {
+        self.center
+    }
+
+    /// Whether `location` lies within the window around the center.
+    pub fn covers(&self, location: IVec3) -> bool {
+        let min = self.center - IVec3::splat(N as i32 / 2);
+        location.cmpge(min).all() && location.cmplt(min + IVec3::splat(N as i32)).all()
+    }
+
+    fn slot(location: IVec3) -> usize {
+        let IVec3 { x, y, z } = location.rem_euclid(IVec3::splat(N as i32));
+        (z as usize * N + y as usize) * N + x as usize
+    }
+
+    pub fn get(&self, location: IVec3) -> Option<&Arc<RwLock<Chunk>>> {
+        match &self.entries[Self::slot(location)] {
+            Some((stored, chunk)) if *stored == location => Some(chunk),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, location: IVec3) -> bool {
+        self.get(location).is_some()
+    }
+
+    /// Stores a chunk, returning it back if `location` is outside the window.
+    pub fn insert(&mut self, location: IVec3, chunk: Chunk) -> Result<(), Chunk> {
+        if !self.covers(location) {
+            return Err(chunk);
+        }
+
+        self.entries[Self::slot(location)] = Some((location, Arc::new(RwLock::new(chunk))));
+        Ok(())
+    }
+
+    pub fn remove(&mut self, location: IVec3) -> Option<Arc<RwLock<Chunk>>> {
+        let entry = &mut self.entries[Self::slot(location)];
+
+        match entry {
+            Some((stored, _)) if *stored == location => entry.take().map(|(_, chunk)| chunk),
+            _ => None,
+        }
+    }
+
+    /// Moves the window, evicting and returning the chunks left outside it.
+    pub fn recenter(&mut self, center: IVec3) -> Vec<(IVec3, Arc<RwLock<Chunk>>)> {
+        self.center = center;
+        let mut evicted = Vec::new();
+
+        for idx in 0..self.entries.len() {
+            if matches!(&self.entries[idx], Some((location, _)) if !self.covers(*location)) {
+                evicted.extend(self.entries[idx].take());
+            }
+        }
+
+        evicted
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (IVec3, &Arc<RwLock<Chunk>>)> {
+        self.entries
+            .iter()
+            .flatten()
+            .map(|(location, chunk)| (*location, chunk))
+    }
+
+    pub fn locations(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.iter().map(|(location, _)| location)
+    }
+}
@@ -1,128 +1,85 @@
-use std::{time::{Instant, Duration}, collections::HashMap, sync::{Arc, atomic::AtomicU32}, f64::consts::SQRT_2, ops::{Index, IndexMut}};
-use noise::{Perlin, NoiseFn};
-use rand_xoshiro::rand_core::{RngCore, SeedableRng};
+use std::{time::{Instant, Duration}, collections::HashMap, array, sync::{Arc, RwLock, atomic::{AtomicU32, AtomicU64, Ordering}}, ops::Index};
 
 use glam::{Vec3, IVec3, ivec3, vec2, vec3};
 
-use crate::{graphics::Vertex, BlockData, assets::Pack, types::{SIDES, SideMap, DirMap, Direction}};
+use crate::{graphics::Vertex, assets::{BlockId, Pack, Quad}, light, palette::PackedCube, pool::Pool, types::{SIDES, DIRECTIONS, SideMap, DirMap, Direction, Cube}};
 
 #[derive(Debug)]
 pub struct Chunk {
 	pub nonce: u32,
-    contents: Box<[[[i16; 32]; 32]; 32]>,
+	pub modified: bool,
+    contents: PackedCube<BlockId>,
+
+	/// Sky light in the high nibble, block light in the low one.
+	light: PackedCube<u8>,
 }
 
-pub static mut MESHING_DURATION: Duration = Duration::ZERO;
-pub static mut MESHING_TIMES: usize = 0;
+static MESHING_NANOS: AtomicU64 = AtomicU64::new(0);
+static MESHING_TIMES: AtomicU64 = AtomicU64::new(0);
+
+/// Average time taken to build a chunk mesh so far.
+pub fn average_meshing_time() -> Duration {
+	let times = MESHING_TIMES.load(Ordering::Relaxed).max(1);
+	Duration::from_nanos(MESHING_NANOS.load(Ordering::Relaxed) / times)
+}
 
 static NONCE: AtomicU32 = AtomicU32::new(0);
 
 fn fresh_nonce() -> u32 {
-	NONCE.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+	NONCE.fetch_add(1, Ordering::Relaxed)
 }
 
 impl Index<IVec3> for Chunk {
-    type Output = i16;
+    type Output = BlockId;
 
     fn index(&self, index: IVec3) -> &Self::Output {
-        let IVec3 { x, y, z } = index & 31;
-
-        unsafe {
-            // Location is already masked into range
-            self.contents
-                .get_unchecked(z as usize)
-                .get_unchecked(y as usize)
-                .get_unchecked(x as usize)
-        }
+        self.contents.get(index)
     }
 }
 
-impl IndexMut<IVec3> for Chunk {
-    fn index_mut(&mut self, index: IVec3) -> &mut Self::Output {
-        let IVec3 { x, y, z } = index & 31;
+impl Chunk {
+	/// Returns whether the block actually changed.
+	pub fn place(&mut self, location: IVec3, block: BlockId) -> bool {
+		let changed = self.contents.set(location, block);
 
-        unsafe {
-            // Location is already masked into range
-            self.contents
-                .get_unchecked_mut(z as usize)
-                .get_unchecked_mut(y as usize)
-                .get_unchecked_mut(x as usize)
-        }
-    }
-}
+		if changed {
+			self.nonce = fresh_nonce();
+			self.modified = true;
+		}
 
-impl Chunk {
-	pub fn place(&mut self, location: IVec3, block: i16) {
-		let IVec3 { x, y, z } = location & 31;
-		self.contents[z as usize][y as usize][x as usize] = block;
+		changed
+	}
+
+	/// Invalidates anything derived from the chunk, such as its mesh.
+	pub fn touch(&mut self) {
 		self.nonce = fresh_nonce();
 	}
 
-	pub fn generate(location: IVec3, pack: &Pack) -> Self {
-		let mut chunk = Self::default();
-		let mut rand = rand_xoshiro::Xoshiro256PlusPlus::seed_from_u64((2 * location.x + 3 * location.y + 5 * location.z) as u64);
-		let perlin1 = Perlin::new(1);
-		let perlin2 = Perlin::new(2);
-		let perlin3 = Perlin::new(3);
-
-		let b_air = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("air")).unwrap();
-		let b_bedrock = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("bedrock.toml")).unwrap();
-		let b_cactus = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("cactus.toml")).unwrap();
-		let b_cobblestone = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("cobblestone.toml")).unwrap();
-		let b_dirt = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("dirt.toml")).unwrap();
-		let b_grass = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("grass.toml")).unwrap();
-		let b_gravel = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("gravel.toml")).unwrap();
-		let b_obsidian = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("obsidian.toml")).unwrap();
-		let b_pumpkin = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("pumpkin.toml")).unwrap();
-		let b_sand = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("sand.toml")).unwrap();
-		let b_stone = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("stone.toml")).unwrap();
-
-        // jmi2k: placeholder to have something nice to test
-        'layer: for k in 0..32 {
-            for j in 0..32 {
-                for i in 0..32 {
-                    let block_loc = ivec3(i as _, j as _, k as _);
-                    let IVec3 { x, y, z } = (location << 5) | block_loc;
-					let fx = x as f64;
-					let fy = y as f64;
-					let factor = SQRT_2 / 1000.;
-					let val1 = perlin1.get([fx * factor, fy * factor]) * 0.9;
-					let val2 = perlin2.get([fx * 10. * factor, fy * 10. * factor]) * 0.09;
-					let val3 = perlin3.get([fx * 100. * factor, fy * 100. * factor]) * 0.009;
-					let val = val1 + val2 + val3;
-					let min_h = -30.;
-					let max_h = 30.;
-					let h = (val * (max_h - min_h) + min_h) as i32;
-					if !(-128..31).contains(&z) {
-						continue 'layer;
-					}
-					let sea = [x, y, z].map(i32::to_be_bytes);
-					let rand = rand.next_u32() as usize;
-
-                    let idx = {
-                        if z == -128 { b_bedrock }
-                        else if z == -127 { [b_stone, b_cobblestone, b_bedrock, b_bedrock][rand % 4] }
-                        else if ((-126..(h - 9)).contains(&z)) { [b_stone, b_cobblestone][(rand >> 2) % 2] }
-                        else if ((h - 9)..(h - 4)).contains(&z) { [b_dirt, b_dirt, b_stone, b_cobblestone][(rand >> 3) % 4] }
-                        else if ((h-4)..h).contains(&z) { b_dirt }
-						else if z == h { [
-							b_grass, b_grass, b_grass, b_grass,
-							b_grass, b_grass, b_grass, b_grass,
-							b_grass, b_grass, b_grass, b_grass,
-							b_grass, b_grass, b_grass, b_grass,
-							b_grass, b_grass, b_grass, b_grass,
-							b_grass, b_grass, b_grass, b_grass,
-							b_grass, b_dirt, b_grass, b_dirt,
-							b_grass, b_air, b_grass, b_cobblestone][(rand >> 5) % 16] }
-                        else { b_air }
-                    };
-
-                    unsafe { *chunk.contents.get_unchecked_mut(block_loc.z as usize).get_unchecked_mut(block_loc.y as usize).get_unchecked_mut(block_loc.x as usize) = idx as i16; }
-                }
-            }
-        }
+	/// Returns the block filling the whole chunk, if there is only one.
+	pub fn uniform(&self) -> Option<BlockId> {
+		self.contents.uniform()
+	}
 
-        chunk
+	pub fn light(&self, location: IVec3) -> u8 {
+		*self.light.get(location)
+	}
+
+	/// Does not invalidate the chunk, lighting takes care of that itself.
+	pub fn set_light(&mut self, location: IVec3, light: u8) -> bool {
+		self.light.set(location, light)
+	}
+
+	pub fn replace_light(&mut self, light: &Cube<u8, 32>) {
+		self.light = PackedCube::from_values(light.iter().flatten().flatten().copied());
+	}
+
+	pub fn from_contents(contents: Box<Cube<BlockId, 32>>) -> Self {
+		Self {
+			nonce: fresh_nonce(),
+			modified: false,
+			contents: PackedCube::from_values(contents.iter().flatten().flatten().copied()),
+			light: PackedCube::Uniform(0),
+		}
 	}
 }
 
@@ -130,87 +87,383 @@ impl Default for Chunk {
     fn default() -> Self {
         Self {
 			nonce: fresh_nonce(),
-            contents: unsafe { Box::new_zeroed().assume_init() },
+			modified: false,
+            contents: PackedCube::Uniform(BlockId::AIR),
+			light: PackedCube::Uniform(0),
         }
     }
 }
 
+/// Copy of a chunk's contents plus a one block border taken from the 26
+/// chunks around it. Blocks of missing neighbours read as air lit by the sky.
+pub struct Padded {
+	pub nonce: u32,
+	pub uniform: Option<BlockId>,
+	blocks: Box<Cube<BlockId, 34>>,
+	light: Box<Cube<u8, 34>>,
+}
+
+impl Padded {
+	/// `chunks` receives offsets in `-1..=1` relative to the padded chunk.
+	pub fn new<'c>(chunks: impl Fn(IVec3) -> Option<&'c Chunk>) -> Self {
+		let neighborhood: [[[Option<&Chunk>; 3]; 3]; 3] = array::from_fn(|k| {
+			array::from_fn(|j| array::from_fn(|i| chunks(ivec3(i as i32 - 1, j as i32 - 1, k as i32 - 1))))
+		});
+
+		let nonce = neighborhood[1][1][1].map_or(0, |chunk| chunk.nonce);
+		let uniform = neighborhood[1][1][1].map_or(Some(BlockId::AIR), Chunk::uniform);
+		let mut blocks: Box<Cube<BlockId, 34>> = unsafe { Box::new_zeroed().assume_init() };
+		let mut light = Box::new([[[light::SKY; 34]; 34]; 34]);
+
+		for k in 0..34 {
+			for j in 0..34 {
+				for i in 0..34 {
+					let location = ivec3(i, j, k) - IVec3::ONE;
+					let IVec3 { x, y, z } = (location >> 5) + IVec3::ONE;
+
+					if let Some(chunk) = neighborhood[z as usize][y as usize][x as usize] {
+						blocks[k as usize][j as usize][i as usize] = chunk[location];
+						light[k as usize][j as usize][i as usize] = chunk.light(location);
+					}
+				}
+			}
+		}
+
+		Self { nonce, uniform, blocks, light }
+	}
+
+	/// Reads the 27 chunks around and including the padded one, ordered by z,
+	/// then y, then x.
+	pub fn from_neighborhood(chunks: &[Option<Arc<RwLock<Chunk>>>; 27]) -> Self {
+		let guards = chunks.each_ref().map(|chunk| chunk.as_ref().map(|chunk| chunk.read().unwrap()));
+
+		Self::new(|offset| {
+			let IVec3 { x, y, z } = offset + IVec3::ONE;
+			guards[(z * 9 + y * 3 + x) as usize].as_deref()
+		})
+	}
+
+	/// Location is relative to the padded chunk, in `-1..=32`.
+	pub fn get(&self, location: IVec3) -> BlockId {
+		let IVec3 { x, y, z } = location + IVec3::ONE;
+		self.blocks[z as usize][y as usize][x as usize]
+	}
+
+	pub fn light(&self, location: IVec3) -> u8 {
+		let IVec3 { x, y, z } = location + IVec3::ONE;
+		self.light[z as usize][y as usize][x as usize]
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MeshingMode {
+	/// One quad per visible face.
+	#[default]
+	Naive,
+
+	/// Merge coplanar faces of full cubes into larger quads.
+	Greedy,
+}
+
+/// What a greedy mesher needs to know to tell whether two faces can merge.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Face {
+	block: BlockId,
+	light: u32,
+	occlusion: [u8; 4],
+}
+
+/// Shading of a vertex for each ambient occlusion level, from fully occluded
+/// to not occluded at all.
+const OCCLUSION_SHADOW: [f32; 4] = [0.5, 0.65, 0.8, 1.];
+
+/// Ambient occlusion level of each vertex of a quad facing `dir`, from the two
+/// sides and the corner next to it in the layer in front of the face.
+fn occlusion(padded: &Padded, pack: &Pack, location: IVec3, dir: Direction, quad: &Quad) -> [u8; 4] {
+	let a = dir.axis();
+	let (u, v) = ((a + 1) % 3, (a + 2) % 3);
+	let facing = location + IVec3::from(dir);
+	let occludes = |offset: IVec3| pack.blocks[padded.get(facing + offset)].culls[dir.opposite()] as u8;
+
+	quad.map(|vertex| {
+		let mut side_u = IVec3::ZERO;
+		let mut side_v = IVec3::ZERO;
+		side_u[u] = if vertex.xyz[u] > 0.5 { 1 } else { -1 };
+		side_v[v] = if vertex.xyz[v] > 0.5 { 1 } else { -1 };
+
+		match (occludes(side_u), occludes(side_v)) {
+			(1, 1) => 0,
+			(s1, s2) => 3 - s1 - s2 - occludes(side_u + side_v),
+		}
+	})
+}
+
+/// Whether the face of a block facing `dir` is covered by its neighbour, be it
+/// one that culls or more of the same translucent block.
+fn hidden(padded: &Padded, pack: &Pack, location: IVec3, dir: Direction) -> bool {
+	let block = padded.get(location);
+	let neighbor = padded.get(location + IVec3::from(dir));
+
+	pack.blocks[neighbor].culls[dir.opposite()] || (block == neighbor && pack.blocks[block].translucent())
+}
+
+fn push_quad(vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, quad: [Vertex; 4], occlusion: [u8; 4]) {
+	let base = vertices.len() as u32;
+	vertices.extend(quad);
+
+	// Split along the brighter diagonal so occlusion interpolates evenly
+	let order = if occlusion[0] + occlusion[2] >= occlusion[1] + occlusion[3] {
+		[0u32, 1, 2, 3, 0, 2]
+	} else {
+		[0u32, 1, 3, 1, 2, 3]
+	};
+
+	indices.extend(order.map(|idx| base + idx));
+}
+
+/// Nonce of the contents meshed, the vertices, then the indices of opaque and
+/// of translucent triangles, which must be drawn separately.
+pub type Mesh = (u32, Vec<Vertex>, Vec<u32>, Vec<u32>);
+
+/// Builds chunk meshes on worker threads, keeping the latest mesh of every
+/// chunk around until its contents change.
 pub struct Mesher {
-	cached_meshes: HashMap<IVec3, Arc<(u32, Vec<Vertex>, Vec<u32>)>>,
+	pool: Pool<(IVec3, u32, [Option<Arc<RwLock<Chunk>>>; 27]), (IVec3, Arc<Mesh>)>,
+
+	/// Nonce of the mesh being built for each chunk. Finished meshes with any
+	/// other nonce are stale and thrown away.
+	pending: HashMap<IVec3, u32>,
+
+	cached_meshes: HashMap<IVec3, Arc<Mesh>>,
 }
 
 impl Mesher {
-	pub fn new() -> Self {
+	pub fn new(mode: MeshingMode, pack: Arc<Pack>, workers: usize) -> Self {
+		let pool = Pool::new(workers, move |(position, _, chunks)| {
+			let padded = Padded::from_neighborhood(&chunks);
+			(position, Arc::new(mode.build_mesh(&padded, position, &pack)))
+		});
+
 		Self {
+			pool,
+			pending: HashMap::default(),
 			cached_meshes: HashMap::default(),
 		}
 	}
 
-    pub fn build_mesh(&mut self, chunk: &Chunk, position: [i32; 3], pack: &Pack) -> Arc<(u32, Vec<Vertex>, Vec<u32>)> {
-		if let Some(entry) = self.cached_meshes.get(&IVec3::from_array(position)) {
-			if entry.0 == chunk.nonce {
-				return entry.clone();
+	pub fn cached(&self, position: IVec3, nonce: u32) -> Option<Arc<Mesh>> {
+		self.cached_meshes
+			.get(&position)
+			.filter(|entry| entry.0 == nonce)
+			.cloned()
+	}
+
+	/// Queues a chunk for meshing unless a mesh of the same contents is
+	/// already cached or on its way. Lower priorities are built first.
+	pub fn request(&mut self, position: IVec3, nonce: u32, chunks: impl FnOnce() -> [Option<Arc<RwLock<Chunk>>>; 27], priority: i64) {
+		if self.cached(position, nonce).is_some() {
+			return;
+		}
+
+		match self.pending.insert(position, nonce) {
+			Some(old) if old == nonce => return,
+
+			// Do not waste a worker on contents that changed since
+			Some(_) => self.pool.retain(|(position, nonce, _)| self.pending.get(position) == Some(nonce)),
+			None => {}
+		}
+
+		self.pool.submit(priority, (position, nonce, chunks()));
+	}
+
+	/// Moves finished meshes into the cache, discarding stale ones.
+	pub fn collect(&mut self) {
+		for (position, mesh) in self.pool.results() {
+			if self.pending.get(&position) == Some(&mesh.0) {
+				self.pending.remove(&position);
+				self.cached_meshes.insert(position, mesh);
 			}
-		};
+		}
+	}
+
+	/// Drops the meshes, finished or not, of chunks for which `keep` is false.
+	pub fn retain(&mut self, mut keep: impl FnMut(IVec3) -> bool) {
+		self.pending.retain(|position, _| keep(*position));
+		self.cached_meshes.retain(|position, _| keep(*position));
+	}
+}
 
-        let [x, y, z] = position;
+impl MeshingMode {
+    fn build_mesh(self, padded: &Padded, position: IVec3, pack: &Pack) -> Mesh {
 		let then = Instant::now();
         let mut vertices = Vec::with_capacity(32_768);
         let mut indices = Vec::with_capacity(65_536);
+        let mut translucent = Vec::new();
+
+		let empty = padded
+			.uniform
+			.is_some_and(|block| SIDES.into_iter().all(|side| pack.blocks[block].mesh[side].is_empty()));
+
+        for k in 0..if empty { 0 } else { 32 } {
+            for j in 0..32 {
+                for i in 0..32 {
+					let location = ivec3(i, j, k);
+					let origin = (position * 32 + location).as_vec3();
+					let id = padded.get(location);
+					let block = &pack.blocks[id];
+					let greedy = self == MeshingMode::Greedy && block.cube;
+					let indices = if block.translucent() { &mut translucent } else { &mut indices };
+
+					for side in SIDES {
+						// Faces are lit by the space they face
+						let mut lit_from = location;
 
-        for (k, layer) in chunk.contents.into_iter().enumerate() {
-            for (j, row) in layer.into_iter().enumerate() {
-                for (i, block) in row.into_iter().enumerate() {
-					let neighbors = DirMap {
-						west: if i > 0 { chunk.contents[k][j][i - 1] } else { 0 },
-						east: if i < 31 { chunk.contents[k][j][i + 1] } else { 0 },
-						south: if j > 0 { chunk.contents[k][j - 1][i] } else { 0 },
-						north: if j < 31 { chunk.contents[k][j + 1][i] } else { 0 },
-						down: if k > 0 { chunk.contents[k - 1][j][i] } else { 0 },
-						up: if k < 31 { chunk.contents[k + 1][j][i] } else { 0 },
-					};
-					let (_, block) = &pack.blocks[block as usize];
-					let mesh = &block.mesh;
-					let quads = SIDES.into_iter().flat_map(|side| {
 						if let Some(dir) = side {
-							let (_, b) = &pack.blocks[neighbors[dir] as usize];
-							if b.culls[dir.opposite()] {
-								[].iter()
-							} else {
-								mesh[side].iter()
+							lit_from += IVec3::from(dir);
+
+							if greedy || hidden(padded, pack, location, dir) {
+								continue;
 							}
-						} else {
-							mesh[side].iter()
 						}
-					});
-					let mut num_vertices = 0;
-					let base = vertices.len() as u32;
-					quads.flatten().map(|vertex| {
-						num_vertices += 1;
-						let mut xyz = vertex.xyz;
-                        xyz += Vec3::new(
-                            32. * x as f32 + i as f32,
-                            32. * y as f32 + j as f32,
-                            32. * z as f32 + k as f32,
-                        );
-						Vertex { xyz, ..*vertex }
-					}).collect_into(&mut vertices);
-					let num_quads = num_vertices / 4;
-
-					(0..num_quads)
-						.flat_map(|n| [0u32, 1, 2, 3, 0, 2].map(|idx| base + 4 * n as u32 + idx))
-						.collect_into(&mut indices);
+
+						let light = light::level(padded.light(lit_from)) as u32;
+
+						for quad in block.mesh[side].iter() {
+							let occlusion = match side {
+								Some(dir) => occlusion(padded, pack, location, dir, quad),
+								None => [3; 4],
+							};
+
+							let mut occlusion_idx = 0;
+
+							push_quad(&mut vertices, indices, quad.map(|vertex| {
+								let shadow = vertex.shadow * OCCLUSION_SHADOW[occlusion[occlusion_idx] as usize];
+								occlusion_idx += 1;
+
+								Vertex {
+									xyz: vertex.xyz + origin,
+									shadow,
+									light,
+									..vertex
+								}
+							}), occlusion);
+						}
+					}
                 }
             }
         }
 
-		unsafe {
-			MESHING_DURATION += then.elapsed();
-			MESHING_TIMES += 1;
+		if self == MeshingMode::Greedy && !empty {
+			for dir in DIRECTIONS {
+				self.merge_faces(padded, position, pack, dir, &mut vertices, &mut indices, &mut translucent);
+			}
 		}
 
-		let mesh = Arc::new((chunk.nonce, vertices, indices));
-		self.cached_meshes.insert(IVec3::from_array(position), mesh.clone());
-		mesh
+		MESHING_NANOS.fetch_add(then.elapsed().as_nanos() as u64, Ordering::Relaxed);
+		MESHING_TIMES.fetch_add(1, Ordering::Relaxed);
+
+		(padded.nonce, vertices, indices, translucent)
     }
+
+	fn face(&self, padded: &Padded, pack: &Pack, location: IVec3, dir: Direction) -> Option<Face> {
+		let block = padded.get(location);
+
+		if !pack.blocks[block].cube || hidden(padded, pack, location, dir) {
+			return None;
+		}
+
+		let light = light::level(padded.light(location + IVec3::from(dir))) as u32;
+		let occlusion = occlusion(padded, pack, location, dir, &pack.blocks[block].mesh[Some(dir)][0]);
+
+		Some(Face { block, light, occlusion })
+	}
+
+	/// Sweeps the chunk in slices perpendicular to `dir`, growing each visible
+	/// full cube face into the largest rectangle of identical faces.
+	fn merge_faces(&self, padded: &Padded, position: IVec3, pack: &Pack, dir: Direction, vertices: &mut Vec<Vertex>, indices: &mut Vec<u32>, translucent: &mut Vec<u32>) {
+		let a = dir.axis();
+		let (u, v) = ((a + 1) % 3, (a + 2) % 3);
+
+		for slice in 0..32 {
+			let mut mask = [[None; 32]; 32];
+
+			for (row, faces) in mask.iter_mut().enumerate() {
+				for (col, face) in faces.iter_mut().enumerate() {
+					let mut location = IVec3::ZERO;
+					location[a] = slice;
+					location[u] = col as i32;
+					location[v] = row as i32;
+					*face = self.face(padded, pack, location, dir);
+				}
+			}
+
+			for row in 0..32 {
+				let mut col = 0;
+
+				while col < 32 {
+					let Some(face) = mask[row][col] else {
+						col += 1;
+						continue;
+					};
+
+					let mut width = 1;
+
+					while col + width < 32 && mask[row][col + width] == Some(face) {
+						width += 1;
+					}
+
+					let mut height = 1;
+
+					while row + height < 32 && mask[row + height][col..col + width].iter().all(|other| *other == Some(face)) {
+						height += 1;
+					}
+
+					for faces in &mut mask[row..row + height] {
+						faces[col..col + width].fill(None);
+					}
+
+					let mut location = IVec3::ZERO;
+					location[a] = slice;
+					location[u] = col as i32;
+					location[v] = row as i32;
+
+					let mut scale = Vec3::ONE;
+					scale[u] = width as f32;
+					scale[v] = height as f32;
+
+					let origin = (position * 32 + location).as_vec3();
+					let block = &pack.blocks[face.block];
+					let quad = block.mesh[Some(dir)][0];
+					let indices = if block.translucent() { &mut *translucent } else { &mut *indices };
+
+					// UVs grow along with the quad so the shader can repeat the tile
+					let e1 = quad[1].xyz - quad[0].xyz;
+					let e3 = quad[3].xyz - quad[0].xyz;
+					let duv1 = quad[1].uv - quad[0].uv;
+					let duv3 = quad[3].uv - quad[0].uv;
+
+					let mut occlusion_idx = 0;
+
+					push_quad(vertices, indices, quad.map(|vertex| {
+						let delta = vertex.xyz * scale - vertex.xyz;
+						let uv = vertex.uv
+							+ delta.dot(e1) / e1.length_squared() * duv1
+							+ delta.dot(e3) / e3.length_squared() * duv3;
+						let shadow = vertex.shadow * OCCLUSION_SHADOW[face.occlusion[occlusion_idx] as usize];
+						occlusion_idx += 1;
+
+						Vertex {
+							xyz: origin + vertex.xyz * scale,
+							uv,
+							shadow,
+							light: face.light,
+							..vertex
+						}
+					}), face.occlusion);
+
+					col += width;
+				}
+			}
+		}
+	}
 }
@@ -11,23 +11,31 @@ use wgpu::{
     BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor, CompareFunction,
     DepthBiasState, DepthStencilState, Extent3d, Face, FragmentState, FrontFace, LoadOp,
     MultisampleState, Operations, PipelineLayoutDescriptor, PolygonMode, PrimitiveState,
-    PrimitiveTopology, RenderPassColorAttachment, RenderPassDepthStencilAttachment,
+    PrimitiveTopology, RenderPassColorAttachment, RenderPass, RenderPassDepthStencilAttachment,
     RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderStages, StencilState,
     SurfaceError, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
     TextureViewDescriptor, VertexBufferLayout, VertexState, VertexStepMode, PushConstantRange, IndexFormat, RenderBundle, RenderBundleEncoder, RenderBundleEncoderDescriptor, RenderBundleDescriptor, RenderBundleDepthStencil, ImageCopyTexture, ImageDataLayout, SamplerDescriptor, AddressMode, FilterMode, TextureViewDimension, TextureSampleType, BindingResource,
 };
 
-use crate::{graphics::{Camera, GraphicsContext}, chunk, assets::N_MIPS};
+use crate::{graphics::{Camera, GraphicsContext}, chunk::Mesh, assets::{N_MIPS, CELL_SIZE}};
 
 const N_SAMPLES: usize = 1;
 
+fn draw<'r>(render_pass: &mut RenderPass<'r>, vertex_buffer: &'r Buffer, index_buffer: &'r Buffer) {
+    let index_count = index_buffer.size() / size_of::<u32>() as u64;
+
+    render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+    render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+    render_pass.draw_indexed(0..index_count as u32, 0, 0..1);
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct PushConstants {
     camera: Mat4,
     viewport: Vec2,
     time: f32,
-    padding: u32,
+    cells: u32,
 }
 
 #[repr(C)]
@@ -37,6 +45,9 @@ pub struct Vertex {
     pub uv: Vec2,
     pub shadow: f32,
     pub light: u32,
+
+    /// Atlas cell to sample from, `uv` is relative to it and wraps around.
+    pub tile: u32,
 }
 
 impl Vertex {
@@ -48,17 +59,24 @@ impl Vertex {
             1 => Float32x2,
             2 => Float32,
             3 => Uint32,
+            4 => Uint32,
         ],
     };
 }
 
 pub struct WorldRenderer {
     epoch: Instant,
+    atlas_cells: u32,
     depth_texture: Texture,
     msaa_texture: Texture,
     atlas_bind_group: BindGroup,
-    vertex_buffers: HashMap<IVec3, (u32, Buffer, Buffer)>,
+    /// Nonce, vertices, and indices of the opaque and translucent triangles of
+    /// every chunk uploaded.
+    vertex_buffers: HashMap<IVec3, (u32, Buffer, Option<Buffer>, Option<Buffer>)>,
     pipeline: RenderPipeline,
+
+    /// Blends over what is already drawn without hiding anything behind.
+    translucent_pipeline: RenderPipeline,
 }
 
 impl WorldRenderer {
@@ -211,8 +229,9 @@ impl WorldRenderer {
             )
         };
 
-        let pipeline = {
-            let shader = device.create_shader_module(include_wgsl!("../../shader.wgsl"));
+        let shader = device.create_shader_module(include_wgsl!("../../shader.wgsl"));
+
+        let create_pipeline = |cull_mode, depth_write_enabled| {
 
             let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: None,
@@ -225,7 +244,7 @@ impl WorldRenderer {
 
             let primitive = PrimitiveState {
                 topology: PrimitiveTopology::TriangleList,
-                cull_mode: Some(Face::Back),
+                cull_mode,
                 front_face: FrontFace::Ccw,
                 polygon_mode: PolygonMode::Fill,
                 ..Default::default()
@@ -250,7 +269,7 @@ impl WorldRenderer {
 
             let depth_stencil = DepthStencilState {
                 format: depth_texture.format(),
-                depth_write_enabled: true,
+                depth_write_enabled,
                 depth_compare: CompareFunction::Less,
                 stencil: StencilState::default(),
                 bias: DepthBiasState::default(),
@@ -268,9 +287,15 @@ impl WorldRenderer {
             })
         };
 
+        // Translucent faces are seen from both sides, say from under water
+        let pipeline = create_pipeline(Some(Face::Back), true);
+        let translucent_pipeline = create_pipeline(None, false);
+
         Self {
             epoch: Instant::now(),
+            atlas_cells: atlases[0].width() / CELL_SIZE,
             pipeline,
+            translucent_pipeline,
             depth_texture,
             msaa_texture,
             atlas_bind_group,
@@ -278,17 +303,20 @@ impl WorldRenderer {
         }
     }
 
-    pub fn add_vertices(&mut self, graphics_context: &GraphicsContext, location: IVec3, mesh: &Arc<(u32, Vec<Vertex>, Vec<u32>)>) {
+    /// Uploads a chunk mesh unless it is already on the GPU. Returns whether
+    /// anything was uploaded.
+    pub fn add_vertices(&mut self, graphics_context: &GraphicsContext, location: IVec3, mesh: &Arc<Mesh>) -> bool {
         if let Some(entry) = self.vertex_buffers.get(&location) {
             if entry.0 == mesh.0 {
-                return;
+                return false;
             }
         }
 
-        let (_, ref vertices, ref indices) = **mesh;
+        let (_, ref vertices, ref indices, ref translucent) = **mesh;
 
         if vertices.is_empty() {
-            return;
+            self.vertex_buffers.remove(&location);
+            return false;
         }
 
         let vertex_buffer = graphics_context
@@ -299,15 +327,21 @@ impl WorldRenderer {
                 usage: BufferUsages::VERTEX,
             });
 
-        let index_buffer = graphics_context
-            .device
-            .create_buffer_init(&BufferInitDescriptor {
-                label: None,
-                contents: bytemuck::cast_slice(indices),
-                usage: BufferUsages::INDEX,
-            });
+        let create_index_buffer = |indices: &[u32]| {
+            (!indices.is_empty()).then(|| {
+                graphics_context
+                    .device
+                    .create_buffer_init(&BufferInitDescriptor {
+                        label: None,
+                        contents: bytemuck::cast_slice(indices),
+                        usage: BufferUsages::INDEX,
+                    })
+            })
+        };
 
-        self.vertex_buffers.insert(location, (mesh.0, vertex_buffer, index_buffer));
+        let index_buffers = (create_index_buffer(indices), create_index_buffer(translucent));
+        self.vertex_buffers.insert(location, (mesh.0, vertex_buffer, index_buffers.0, index_buffers.1));
+        true
     }
 
     pub fn remove_vertices(&mut self, location: IVec3, distance: i32) {
@@ -413,16 +447,33 @@ impl WorldRenderer {
             camera: Mat4::from(camera),
             viewport,
             time: self.epoch.elapsed().as_secs_f32(),
-            padding: 0,
+            cells: self.atlas_cells,
         }]));
         render_pass.set_bind_group(0, &self.atlas_bind_group, &[]);
 
-        for (_, vertex_buffer, index_buffer) in self.vertex_buffers.values() {
-            let index_count = index_buffer.size() / size_of::<u32>() as u64;
+        for (_, vertex_buffer, index_buffer, _) in self.vertex_buffers.values() {
+            if let Some(index_buffer) = index_buffer {
+                draw(&mut render_pass, vertex_buffer, index_buffer);
+            }
+        }
+
+        // Blending only comes out right from back to front
+        let eye = camera.pov.position;
+        let mut translucent = self
+            .vertex_buffers
+            .iter()
+            .filter_map(|(location, (_, vertex_buffer, _, index_buffer))| Some((location, vertex_buffer, index_buffer.as_ref()?)))
+            .collect::<Vec<_>>();
+
+        translucent.sort_unstable_by(|(a, ..), (b, ..)| {
+            let center = |location: &IVec3| (*location * 32 + 16).as_vec3();
+            eye.distance_squared(center(b)).total_cmp(&eye.distance_squared(center(a)))
+        });
+
+        render_pass.set_pipeline(&self.translucent_pipeline);
 
-            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
-            render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
-            render_pass.draw_indexed(0..index_count as u32, 0, 0..1);
+        for (_, vertex_buffer, index_buffer) in translucent {
+            draw(&mut render_pass, vertex_buffer, index_buffer);
         }
 
         drop(render_pass);
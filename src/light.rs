@@ -0,0 +1,304 @@
+use std::collections::{HashSet, VecDeque};
+
+use glam::{ivec3, IVec3};
+
+use crate::{
+    assets::{BlockId, Pack},
+    types::{Cube, Direction, DIRECTIONS},
+    world::{self, World},
+};
+
+pub const MAX_LIGHT: u8 = 15;
+
+/// Light coming straight from the sky.
+pub const SKY: u8 = MAX_LIGHT << 4;
+
+pub fn sky_light(light: u8) -> u8 {
+    light >> 4
+}
+
+pub fn block_light(light: u8) -> u8 {
+    light & 15
+}
+
+pub fn compose(sky: u8, block: u8) -> u8 {
+    sky << 4 | block
+}
+
+/// Light a face is drawn with, the brightest of both channels.
+pub fn level(light: u8) -> u8 {
+    sky_light(light).max(block_light(light))
+}
+
+/// Light a neighbour in `dir` receives from a cell lit with `light`. Full sky
+/// light travels downwards without fading.
+fn spread_to(light: u8, dir: Direction) -> u8 {
+    let sky = match (sky_light(light), dir) {
+        (MAX_LIGHT, Direction::Down) => MAX_LIGHT,
+        (sky, _) => sky.saturating_sub(1),
+    };
+
+    compose(sky, block_light(light).saturating_sub(1))
+}
+
+fn brightest(a: u8, b: u8) -> u8 {
+    compose(sky_light(a).max(sky_light(b)), block_light(a).max(block_light(b)))
+}
+
+/// Flood-fills light across the loaded chunks, remembering which chunks need
+/// their meshes rebuilt.
+struct Lighter<'w> {
+    world: &'w mut World,
+    pack: &'w Pack,
+    touched: HashSet<IVec3>,
+}
+
+impl<'w> Lighter<'w> {
+    fn new(world: &'w mut World, pack: &'w Pack) -> Self {
+        Self {
+            world,
+            pack,
+            touched: HashSet::default(),
+        }
+    }
+
+    /// Returns the block and light at a world location, if loaded.
+    fn get(&self, location: IVec3) -> Option<(BlockId, u8)> {
+        let chunk_loc: IVec3 = location >> 5;
+        let chunk = self.world.chunk(chunk_loc)?;
+
+        Some((chunk[location], chunk.light(location)))
+    }
+
+    fn set(&mut self, location: IVec3, light: u8) {
+        let chunk_loc: IVec3 = location >> 5;
+
+        let changed = match self.world.chunk_mut(chunk_loc) {
+            Some(mut chunk) => chunk.set_light(location, light),
+            None => false,
+        };
+
+        if changed {
+            self.touched.extend(world::sharing_chunks(location));
+        }
+    }
+
+    fn opaque(&self, block: BlockId) -> bool {
+        self.pack.blocks[block].opaque()
+    }
+
+    fn emits(&self, block: BlockId) -> u8 {
+        self.pack.blocks[block].emits
+    }
+
+    /// Pushes light outwards from every location in `queue`.
+    fn spread(&mut self, mut queue: VecDeque<IVec3>) {
+        while let Some(location) = queue.pop_front() {
+            let Some((_, light)) = self.get(location) else {
+                continue;
+            };
+
+            for dir in DIRECTIONS {
+                let neighbor = location + IVec3::from(dir);
+
+                let Some((block, old)) = self.get(neighbor) else {
+                    continue;
+                };
+
+                if self.opaque(block) {
+                    continue;
+                }
+
+                let new = brightest(old, spread_to(light, dir));
+
+                if new != old {
+                    self.set(neighbor, new);
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+    }
+
+    /// Darkens everything lit through the given locations, whose light has
+    /// already been zeroed, then refills the darkened area from its edges.
+    fn unspread(&mut self, seeds: impl IntoIterator<Item = (IVec3, u8)>) {
+        let mut queue = VecDeque::from_iter(seeds);
+        let mut refill = VecDeque::new();
+
+        while let Some((location, removed)) = queue.pop_front() {
+            for dir in DIRECTIONS {
+                let neighbor = location + IVec3::from(dir);
+
+                let Some((id, old)) = self.get(neighbor) else {
+                    continue;
+                };
+
+                // Only light that could have come from here goes away
+                let lost = spread_to(removed, dir);
+                let sky = if sky_light(old) <= sky_light(lost) { 0 } else { sky_light(old) };
+                let block = if block_light(old) <= block_light(lost) { 0 } else { block_light(old) };
+                let new = compose(sky, block.max(self.emits(id)));
+
+                if new != old {
+                    let removed = compose(
+                        if sky == sky_light(old) { 0 } else { sky_light(old) },
+                        if block == block_light(old) { 0 } else { block_light(old) },
+                    );
+
+                    self.set(neighbor, new);
+                    queue.push_back((neighbor, removed));
+                }
+
+                if new != 0 {
+                    refill.push_back(neighbor);
+                }
+            }
+        }
+
+        self.spread(refill);
+    }
+
+    fn finish(self) {
+        for chunk_loc in self.touched {
+            if let Some(mut chunk) = self.world.chunk_mut(chunk_loc) {
+                chunk.touch();
+            }
+        }
+    }
+}
+
+/// Lights a chunk just added to the world and spreads its light into, and
+/// from, the chunks around it.
+pub fn light_chunk(world: &mut World, location: IVec3, pack: &Pack) {
+    let mut light: Box<Cube<u8, 32>> = unsafe { Box::new_zeroed().assume_init() };
+    let above = world.chunk(location + IVec3::Z);
+
+    let Some(chunk) = world.chunk(location) else {
+        return;
+    };
+
+    let opaque = |block: BlockId| pack.blocks[block].opaque();
+
+    for j in 0..32 {
+        for i in 0..32 {
+            // Without anything above, assume open sky
+            let mut lit = above.as_ref().map_or(true, |above| sky_light(above.light(ivec3(i, j, 0))) == MAX_LIGHT);
+
+            for k in (0..32).rev() {
+                let block = chunk[ivec3(i, j, k)];
+                lit &= !opaque(block);
+
+                let sky = if lit { MAX_LIGHT } else { 0 };
+                light[k as usize][j as usize][i as usize] = compose(sky, pack.blocks[block].emits);
+            }
+        }
+    }
+
+    // Fill the chunk using plain arrays before touching the rest of the world,
+    // unless it is evenly lit already
+    let mut queue = VecDeque::new();
+    let even = chunk.uniform().is_some() && light.iter().flatten().flatten().all(|l| *l == light[0][0][0]);
+
+    for k in 0..if even { 0 } else { 32 } {
+        for j in 0..32 {
+            for i in 0..32 {
+                if light[k][j][i] != 0 {
+                    queue.push_back(ivec3(i as i32, j as i32, k as i32));
+                }
+            }
+        }
+    }
+
+    while let Some(block_loc) = queue.pop_front() {
+        let current = light[block_loc.z as usize][block_loc.y as usize][block_loc.x as usize];
+
+        for dir in DIRECTIONS {
+            let neighbor = block_loc + IVec3::from(dir);
+
+            if neighbor.cmplt(IVec3::ZERO).any() || neighbor.cmpgt(IVec3::splat(31)).any() || opaque(chunk[neighbor]) {
+                continue;
+            }
+
+            let old = light[neighbor.z as usize][neighbor.y as usize][neighbor.x as usize];
+            let new = brightest(old, spread_to(current, dir));
+
+            if new != old {
+                light[neighbor.z as usize][neighbor.y as usize][neighbor.x as usize] = new;
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    drop((above, chunk));
+    world.chunk_mut(location).unwrap().replace_light(&light);
+
+    let mut lighter = Lighter::new(world, pack);
+    let mut queue = VecDeque::new();
+    let mut darkened = Vec::new();
+
+    // Exchange light across the six faces of the chunk
+    for dir in DIRECTIONS {
+        let normal = IVec3::from(dir);
+        let a = dir.axis();
+        let (u, v) = ((a + 1) % 3, (a + 2) % 3);
+
+        for col in 0..32 {
+            for row in 0..32 {
+                let mut inside = IVec3::ZERO;
+                inside[a] = if normal[a] > 0 { 31 } else { 0 };
+                inside[u] = col;
+                inside[v] = row;
+
+                let inside = (location << 5) + inside;
+                let outside = inside + normal;
+
+                let (Some((_, ours)), Some((id, theirs))) = (lighter.get(inside), lighter.get(outside)) else {
+                    continue;
+                };
+
+                // The chunk below may have assumed open sky where now there is none
+                if dir == Direction::Down && sky_light(theirs) == MAX_LIGHT && sky_light(ours) != MAX_LIGHT {
+                    lighter.set(outside, compose(0, block_light(theirs)));
+                    darkened.push((outside, compose(MAX_LIGHT, 0)));
+                    continue;
+                }
+
+                if !lighter.opaque(id) && brightest(theirs, spread_to(ours, dir)) != theirs {
+                    queue.push_back(inside);
+                }
+
+                if brightest(ours, spread_to(theirs, dir.opposite())) != ours {
+                    queue.push_back(outside);
+                }
+            }
+        }
+    }
+
+    lighter.unspread(darkened);
+    lighter.spread(queue);
+    lighter.finish();
+}
+
+/// Relights the surroundings of blocks that just changed.
+pub fn update(world: &mut World, locations: &[IVec3], pack: &Pack) {
+    let mut lighter = Lighter::new(world, pack);
+    let mut darkened = Vec::new();
+    let mut queue = VecDeque::new();
+
+    for &location in locations {
+        let Some((id, old)) = lighter.get(location) else {
+            continue;
+        };
+
+        let emits = lighter.emits(id);
+        lighter.set(location, compose(0, emits));
+        darkened.push((location, old));
+
+        queue.push_back(location);
+        queue.extend(DIRECTIONS.map(|dir| location + IVec3::from(dir)));
+    }
+
+    lighter.unspread(darkened);
+    lighter.spread(queue);
+    lighter.finish();
+}
@@ -9,18 +9,26 @@
 // jmi2k: coordinate system seems backwards (culling, default direction camera points to)
 
 mod assets;
+mod cache;
 mod chunk;
 mod graphics;
 mod input;
+mod light;
+mod palette;
+mod pool;
+mod seed;
+mod storage;
+mod streaming;
+mod terrain;
 mod types;
 mod world;
 
-use std::{time::{Duration, Instant}, f32::consts::PI};
+use std::{env, io::ErrorKind, ops::Range, process, sync::Arc, time::{Duration, Instant}, f32::consts::PI};
 
-use chunk::Chunk;
-use glam::{Quat, Vec3, ivec3, IVec3, ivec2};
+use glam::{Quat, Vec3, IVec3, ivec2};
 use graphics::{Camera, GraphicsContext, Pov, Projection, Vertex, WorldRenderer};
 use input::{Action, Direction3, Input, InputHandler};
+use streaming::ChunkStreamer;
 use rand_xoshiro::rand_core::{SeedableRng, RngCore};
 use winit::{
     event::*,
@@ -29,7 +37,13 @@ use winit::{
 };
 use world::World;
 
-use crate::chunk::{MESHING_TIMES, MESHING_DURATION, Mesher};
+use crate::{assets::BlockId, chunk::{Mesher, MeshingMode}};
+
+/// Chunk meshes uploaded to the GPU at most per frame.
+const UPLOADS_PER_FRAME: usize = 16;
+
+/// Height of the camera above the ground being stood on.
+const EYE_HEIGHT: f32 = 1.6;
 
 #[derive(Debug, Default)]
 pub struct CameraController {
@@ -51,11 +65,16 @@ impl CameraController {
         self.direction -= Vec3::from_array(direction.into());
     }
 
-    pub fn tick(&mut self, delta: Duration) {
+    /// Moves the camera, keeping it within the heights blocks may exist at
+    /// or standing right on top of them.
+    pub fn tick(&mut self, delta: Duration, bounds: Range<i32>) {
         let true_direction =
             Quat::from_rotation_z(-self.camera.pov.yaw) * self.direction.normalize_or_zero();
 
         self.camera.walk(true_direction * delta.as_secs_f32() * 6.);
+
+        let position = &mut self.camera.pov.position;
+        position.z = position.z.clamp(bounds.start as f32, bounds.end as f32 + EYE_HEIGHT);
     }
 }
 
@@ -68,28 +87,22 @@ impl From<Camera> for CameraController {
     }
 }
 
-#[repr(i16)]
-#[derive(Default)]
-pub enum BlockId {
-    #[default]
-    Air,
-
-    Bedrock,
-    Stone,
-    Dirt,
-    Grass,
-}
-
-#[derive(Default)]
-pub struct BlockData {
-    pub id: BlockId,
-}
-
 #[pollster::main]
 async fn main() {
     let mut event_loop = EventLoop::new();
     let window = WindowBuilder::new().build(&event_loop).unwrap();
-    let pack = assets::open("pack").unwrap();
+    let pack = match assets::open("pack") {
+        Ok(pack) => Arc::new(pack),
+
+        Err(errors) => {
+            for error in &errors {
+                eprintln!("{error}");
+            }
+
+            eprintln!("failed to load pack, {} errors", errors.len());
+            process::exit(1);
+        }
+    };
     let mut graphics_context = GraphicsContext::new(&window).await;
     let mut world_renderer = WorldRenderer::new(&graphics_context, &pack.atlases);
 
@@ -122,111 +135,54 @@ async fn main() {
     };
 
     let mut start = Instant::now();
-    let mut world = World::default();
 
-    let then = Instant::now();
+    // Seeds can be given as the first argument or through DITCH_SEED, and only
+    // matter when creating a new world
+    let seed = env::args()
+        .nth(1)
+        .or_else(|| env::var("DITCH_SEED").ok())
+        .map_or_else(rand::random, |text| seed::parse(&text));
+
+    // Terrain such as `flat` or `superflat:bedrock,3*dirt,grass`, see the
+    // terrain registry for every option
+    let terrain = env::var("DITCH_TERRAIN").unwrap_or_else(|_| "default".to_string());
+
+    let terrains = terrain::Registry::default();
+
+    let mut world = match World::open("world", seed, &terrain, &terrains, &pack) {
+        Ok(world) => world,
+
+        Err(err) => {
+            eprintln!("failed to open world: {err}");
 
-    for k in -8..8 {
-        for j in -16..16 {
-            for i in -16..16 {
-                world.loaded_chunks.insert([i, j, k], Chunk::generate(ivec3(i, j, k), &pack));
+            if err.kind() == ErrorKind::InvalidInput {
+                eprintln!("terrain generators: {}", terrains.names().join(", "));
             }
-        }
-    }
 
-    let ba = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("air")).unwrap() as i16;
-    let bw = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("wood.toml")).unwrap() as i16;
-    let bl = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("leaves.toml")).unwrap() as i16;
-    let b_grass = pack.blocks.binary_search_by(|(n, _)| n.as_str().cmp("grass.toml")).unwrap() as i16;
-
-    let tree_model = [
-        [
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, ba, ba, bw, ba, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-        ],
-        [
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, ba, bl, bw, ba, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-        ],
-        [
-            [ba, ba, ba, bl, ba, ba, ba],
-            [ba, bl, bl, bl, bl, ba, ba],
-            [bl, bl, bw, bw, ba, bl, ba],
-            [ba, bl, bl, bl, bl, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-        ],
-        [
-            [ba, ba, bl, bl, bl, ba, ba],
-            [bl, bl, bl, bw, bl, bl, ba],
-            [bl, bw, bl, bw, bw, bl, ba],
-            [bl, bl, bl, bl, bl, bl, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-        ],
-        [
-            [ba, ba, bl, bl, bl, ba, ba],
-            [ba, bl, bl, bw, bl, bl, ba],
-            [bl, bl, bl, bw, bl, bw, bl],
-            [ba, bl, bl, bl, bl, bl, ba],
-            [ba, ba, ba, bl, ba, ba, ba],
-        ],
-        [
-            [ba, ba, ba, bl, ba, ba, ba],
-            [ba, bl, bl, bl, bl, bl, ba],
-            [ba, bl, bw, bw, bl, bw, bl],
-            [ba, bl, bl, bw, bl, bl, ba],
-            [ba, ba, bl, bl, bl, ba, ba],
-        ],
-        [
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, bl, bl, bl, bl, bl, ba],
-            [ba, bl, bw, bw, bl, bl, ba],
-            [ba, bl, bl, bl, bl, bl, ba],
-            [ba, ba, ba, bl, ba, ba, ba],
-        ],
-        [
-            [ba, ba, ba, ba, ba, ba, ba],
-            [ba, ba, bl, bl, ba, ba, ba],
-            [ba, bl, bl, bl, bl, ba, ba],
-            [ba, ba, bl, bl, ba, ba, ba],
-            [ba, ba, ba, ba, ba, ba, ba],
-        ],
-    ];
-
-    println!("terraforming {:?} average", then.elapsed() / (16*32*32));
-
-    let mut initial_h = 255;
-    loop {
-        let loc = ivec3(0, 0, initial_h);
-        let loc2: IVec3 = loc >> 5;
-        let loc2 = loc2.to_array();
-        if world.loaded_chunks.get(&loc2).unwrap()[loc & 31] != 0 {
-            initial_h += 1;
-            break;
+            process::exit(1);
         }
-        initial_h -= 1;
-    }
+    };
+    println!("seed {}", world.seed);
+
+    let then = Instant::now();
+
+    // Chunks stream in around the spawn point, which only depends on the seed.
+    // Worlds without any ground spawn at the origin
+    let spawn = world.find_spawn(world.seed, &pack).unwrap_or(IVec3::ZERO);
+
+    // Loading and meshing share the cores left over by this thread
+    let workers = pool::default_workers();
+    let mut streamer = ChunkStreamer::new(&world, pack.clone(), 10, workers / 2);
+
+    println!("spawning at {spawn} after {:?}", then.elapsed());
+
     let mut camera_controller = CameraController {
         direction: Default::default(),
         camera: Camera {
-            // pov: Pov {
-            //     position: (0., 0., initial_h as f32 + 2.).into(),
-            //     yaw: 0.,
-            //     pitch: 0.,
-            // },
             pov: Pov {
-                position: Vec3::new(
-                    0.0,
-                    0.0,
-                    5.104397,
-                ),
-                yaw: 1.5799987,
-                pitch: 0.33499983,
+                position: spawn.as_vec3() + Vec3::new(0.5, 0.5, EYE_HEIGHT),
+                yaw: 0.,
+                pitch: 0.,
             },
             projection: Projection::Perspective {
                 aspect: window.inner_size().width as f32 / window.inner_size().height as f32,
@@ -238,8 +194,13 @@ async fn main() {
     let mut micros = 0u128;
     let mut frames = 0;
 
-    let mut mesher = Mesher::new();
-    let mut selected_item = 0;
+    let meshing_mode = match env::var("DITCH_MESHER").as_deref() {
+        Ok("greedy") => MeshingMode::Greedy,
+        _ => MeshingMode::Naive,
+    };
+
+    let mut mesher = Mesher::new(meshing_mode, pack.clone(), workers - workers / 2);
+    let mut selected_item = BlockId::AIR;
 
     event_loop.run_return(move |event, _, control_flow| {
         let mut action = Action::Nop;
@@ -250,13 +211,20 @@ async fn main() {
             Event::RedrawRequested(_) => {
                 let then = Instant::now();
 
-                for (location, mesh) in world.build_meshes(&mut mesher, camera_controller.camera.pov.position.as_ivec3(), &pack, distance) {
-                    world_renderer.add_vertices(&graphics_context, location, &mesh);
+                let mut uploads = 0;
+
+                for (location, mesh) in world.build_meshes(&mut mesher, camera_controller.camera.pov.position.as_ivec3(), distance) {
+                    if uploads == UPLOADS_PER_FRAME {
+                        break;
+                    }
+
+                    if world_renderer.add_vertices(&graphics_context, location, &mesh) {
+                        uploads += 1;
+                    }
                 }
 
                 world_renderer.remove_vertices(camera_controller.camera.pov.position.as_ivec3(), distance);
 
-                //println!("meshing {:?} average", unsafe { MESHING_DURATION / MESHING_TIMES as u32 });
                 let then = Instant::now();
 
                 world_renderer
@@ -272,7 +240,8 @@ async fn main() {
                 let delta = start.elapsed();
                 start = Instant::now();
 
-                camera_controller.tick(delta);
+                camera_controller.tick(delta, world.bounds());
+                streamer.update(&mut world, camera_controller.camera.pov.position, &pack, 8);
                 window.request_redraw()
             }
 
@@ -293,12 +262,9 @@ async fn main() {
                 let mut pos0 = poss[0];
                 for pos in &poss[1..] {
                     let loc = pos.as_ivec3();
-                    let x: IVec3 = loc >> 5;
-                    let x = x.to_array();
-                    let block = world.loaded_chunks.get(&x).unwrap()[loc];
-                    if block != 0 {
+                    if world.get_block(loc).is_some_and(|block| block != BlockId::AIR) {
                         let location = loc + IVec3::Z;
-                        world.loaded_chunks.get_mut(&x).unwrap().place(location, selected_item as i16);
+                        world.set_block(location, selected_item, &pack);
                         break;
                     }
                     pos0 = *pos;
@@ -313,8 +279,9 @@ async fn main() {
             Action::Select => {
                 let (_, dy) = input_handler.scroll_delta();
                 let count = dy as isize;
-                selected_item = (selected_item + count).rem_euclid(pack.blocks.len() as isize);
-                println!("selected item is {}", pack.blocks[selected_item as usize].0);
+                let idx = (selected_item.0 as isize + count).rem_euclid(pack.blocks.len() as isize);
+                selected_item = BlockId(idx as u16);
+                println!("selected item is {} ({})", pack.blocks[selected_item].name, pack.blocks.name(selected_item));
             }
 
             Action::Fullscreen if window.fullscreen().is_none() => {
@@ -334,7 +301,9 @@ async fn main() {
                 };
             }
             Action::ExitGame => {
+                world.save().unwrap();
                 println!("{} fps average", 1_000_000. / (micros / frames) as f32);
+                println!("{:?} meshing {:?} average", meshing_mode, chunk::average_meshing_time());
                 println!("{:#?}", camera_controller.camera.pov);
                 *control_flow = ControlFlow::Exit
             },
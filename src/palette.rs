@@ -0,0 +1,222 @@
+use glam::IVec3;
+
+const VOLUME: usize = 32 * 32 * 32;
+
+/// A 32×32×32 cube of values stored as a palette plus bit-packed indices
+/// into it. Cubes holding a single value need no index storage at all.
+#[derive(Debug, Clone)]
+pub enum PackedCube<T> {
+    Uniform(T),
+
+    Packed {
+        palette: Vec<T>,
+        bits: u32,
+        words: Box<[u64]>,
+    },
+}
+
+fn offset(location: IVec3) -> usize {
+    let IVec3 { x, y, z } = location & 31;
+    (z as usize) << 10 | (y as usize) << 5 | x as usize
+}
+
+impl<T: Copy + Eq> PackedCube<T> {
+    /// Packs values given in z, y, x order.
+    pub fn from_values(values: impl IntoIterator<Item = T>) -> Self {
+        let mut palette = Vec::<T>::new();
+        let mut last = 0;
+
+        let indices = values
+            .into_iter()
+            .map(|value| {
+                if palette.get(last) != Some(&value) {
+                    last = match palette.iter().position(|entry| *entry == value) {
+                        Some(entry) => entry,
+                        None => {
+                            palette.push(value);
+                            palette.len() - 1
+                        }
+                    };
+                }
+
+                last
+            })
+            .collect::<Vec<_>>();
+
+        debug_assert!(indices.len() == VOLUME, "incorrect cube size");
+
+        if palette.len() == 1 {
+            return Self::Uniform(palette[0]);
+        }
+
+        // Keep entries from straddling two words
+        let bits = (usize::BITS - (palette.len() - 1).leading_zeros()).next_power_of_two();
+        let per_word = 64 / bits as usize;
+        let mut words = vec![0u64; VOLUME / per_word].into_boxed_slice();
+
+        for (idx, entry) in indices.into_iter().enumerate() {
+            words[idx / per_word] |= (entry as u64) << (bits as usize * (idx % per_word));
+        }
+
+        Self::Packed {
+            palette,
+            bits,
+            words,
+        }
+    }
+
+    pub fn uniform(&self) -> Option<T> {
+        match self {
+            Self::Uniform(value) => Some(*value),
+            Self::Packed { .. } => None,
+        }
+    }
+
+    pub fn get(&self, location: IVec3) -> &T {
+        match self {
+            Self::Uniform(value) => value,
+
+            Self::Packed {
+                palette,
+                bits,
+                words,
+            } => {
+                let idx = offset(location);
+                let per_word = 64 / *bits as usize;
+                let shift = *bits as usize * (idx % per_word);
+                let entry = (words[idx / per_word] >> shift) & ((1 << bits) - 1);
+
+                &palette[entry as usize]
+            }
+        }
+    }
+
+    /// Returns whether the stored value actually changed.
+    pub fn set(&mut self, location: IVec3, value: T) -> bool {
+        if *self.get(location) == value {
+            return false;
+        }
+
+        let fits = match self {
+            Self::Uniform(_) => false,
+
+            Self::Packed { palette, bits, .. } => {
+                palette.contains(&value) || palette.len() < 1 << *bits
+            }
+        };
+
+        if !fits {
+            // Rebuilding drops stale palette entries and picks a new width
+            let target = offset(location);
+            let values = (0..VOLUME)
+                .map(|idx| {
+                    if idx == target {
+                        value
+                    } else {
+                        *self.get(IVec3::new(idx as i32 & 31, idx as i32 >> 5 & 31, idx as i32 >> 10))
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            *self = Self::from_values(values);
+            return true;
+        }
+
+        let Self::Packed { palette, bits, words } = self else {
+            unreachable!()
+        };
+
+        let entry = match palette.iter().position(|entry| *entry == value) {
+            Some(entry) => entry,
+            None => {
+                palette.push(value);
+                palette.len() - 1
+            }
+        };
+
+        let idx = offset(location);
+        let per_word = 64 / *bits as usize;
+        let shift = *bits as usize * (idx % per_word);
+        let mask = ((1u64 << *bits) - 1) << shift;
+        let word = &mut words[idx / per_word];
+
+        *word = (*word & !mask) | ((entry as u64) << shift);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location(idx: usize) -> IVec3 {
+        IVec3::new(idx as i32 & 31, idx as i32 >> 5 & 31, idx as i32 >> 10)
+    }
+
+    fn width(cube: &PackedCube<u16>) -> Option<(usize, u32)> {
+        match cube {
+            PackedCube::Uniform(_) => None,
+            PackedCube::Packed { palette, bits, .. } => Some((palette.len(), *bits)),
+        }
+    }
+
+    #[test]
+    fn packs_values_in_order() {
+        let values = (0..VOLUME).map(|idx| (idx % 7 * 3) as u16).collect::<Vec<_>>();
+        let cube = PackedCube::from_values(values.iter().copied());
+
+        assert_eq!(width(&cube), Some((7, 4)));
+        assert!((0..VOLUME).all(|idx| *cube.get(location(idx)) == values[idx]));
+        assert_eq!(PackedCube::from_values([5u16; VOLUME]).uniform(), Some(5));
+    }
+
+    #[test]
+    fn grows_and_repacks() {
+        let mut values = vec![0u16; VOLUME];
+        let mut cube = PackedCube::Uniform(0);
+
+        assert!(!cube.set(location(9), 0));
+
+        // Widths only ever take powers of two, so entries never straddle words
+        for (value, expected) in [(1, (2, 1)), (2, (3, 2)), (3, (4, 2)), (4, (5, 4))] {
+            let idx = value as usize * 4099;
+            assert!(cube.set(location(idx), value));
+            values[idx] = value;
+            assert_eq!(width(&cube), Some(expected));
+        }
+
+        assert!((0..VOLUME).all(|idx| *cube.get(location(idx)) == values[idx]));
+
+        // Replaced values linger until the palette runs out of room
+        for value in 1..=4 {
+            cube.set(location(value as usize * 4099), 0);
+        }
+
+        assert_eq!(width(&cube), Some((5, 4)));
+
+        for value in 5..=16 {
+            cube.set(location(value as usize), value);
+        }
+
+        assert_eq!(width(&cube), Some((13, 4)));
+        assert!((1..=4).all(|value| *cube.get(location(value * 4099)) == 0));
+        assert!((5..=16).all(|value| *cube.get(location(value as usize)) == value));
+    }
+
+    #[test]
+    fn repacking_drops_stale_entries() {
+        let mut cube = PackedCube::Uniform(0u16);
+
+        for idx in 0..VOLUME {
+            cube.set(location(idx), 3);
+        }
+
+        assert_eq!(width(&cube), Some((2, 1)));
+
+        // No room left for a third value, and nothing holds zero anymore
+        assert!(cube.set(location(0), 5));
+        assert_eq!(width(&cube), Some((2, 1)));
+        assert_eq!(*cube.get(location(0)), 5);
+        assert!((1..VOLUME).all(|idx| *cube.get(location(idx)) == 3));
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        mpsc::{self, Receiver, TryIter},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// Worker count that leaves a core for the main thread, to be split among
+/// every pool.
+pub fn default_workers() -> usize {
+    thread::available_parallelism().map_or(1, |count| count.get().saturating_sub(1))
+}
+
+struct Job<J> {
+    priority: i64,
+    seq: u64,
+    job: J,
+}
+
+impl<J> PartialEq for Job<J> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<J> Eq for Job<J> {}
+
+impl<J> PartialOrd for Job<J> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<J> Ord for Job<J> {
+    // Lowest priority value first, oldest first among equals
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.priority, other.seq).cmp(&(self.priority, self.seq))
+    }
+}
+
+struct Queue<J> {
+    jobs: BinaryHeap<Job<J>>,
+    seq: u64,
+    closed: bool,
+}
+
+struct Shared<J> {
+    queue: Mutex<Queue<J>>,
+    ready: Condvar,
+}
+
+/// Fixed set of worker threads, always picking the job with the lowest
+/// priority value next. Results are collected without blocking.
+pub struct Pool<J, R> {
+    shared: Arc<Shared<J>>,
+    results: Receiver<R>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<J: Send + 'static, R: Send + 'static> Pool<J, R> {
+    /// Starts `workers` threads running `work`, at least one even when there
+    /// are no workers to spare.
+    pub fn new(workers: usize, work: impl Fn(J) -> R + Send + Sync + 'static) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                jobs: BinaryHeap::new(),
+                seq: 0,
+                closed: false,
+            }),
+            ready: Condvar::new(),
+        });
+
+        let work = Arc::new(work);
+        let (sender, results) = mpsc::channel();
+
+        let workers = (0..workers.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                let work = work.clone();
+                let sender = sender.clone();
+
+                thread::spawn(move || loop {
+                    let job = {
+                        let mut queue = shared.queue.lock().unwrap();
+
+                        loop {
+                            if queue.closed {
+                                return;
+                            }
+
+                            if let Some(Job { job, .. }) = queue.jobs.pop() {
+                                break job;
+                            }
+
+                            queue = shared.ready.wait(queue).unwrap();
+                        }
+                    };
+
+                    if sender.send(work(job)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            shared,
+            results,
+            workers,
+        }
+    }
+
+    pub fn submit(&self, priority: i64, job: J) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let seq = queue.seq;
+
+        queue.seq += 1;
+        queue.jobs.push(Job { priority, seq, job });
+        self.shared.ready.notify_one();
+    }
+
+    /// Recomputes the priority of every queued job, dropping those for which
+    /// `priority` returns `None`. Dropped jobs are returned.
+    pub fn reprioritize(&self, mut priority: impl FnMut(&J) -> Option<i64>) -> Vec<J> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let mut dropped = Vec::new();
+
+        let jobs = queue
+            .jobs
+            .drain()
+            .filter_map(|job| match priority(&job.job) {
+                Some(priority) => Some(Job { priority, ..job }),
+
+                None => {
+                    dropped.push(job.job);
+                    None
+                }
+            })
+            .collect();
+
+        queue.jobs = jobs;
+        dropped
+    }
+
+    /// Drops every queued job for which `keep` returns `false`.
+    pub fn retain(&self, mut keep: impl FnMut(&J) -> bool) {
+        self.shared.queue.lock().unwrap().jobs.retain(|job| keep(&job.job));
+    }
+
+    pub fn results(&self) -> TryIter<'_, R> {
+        self.results.try_iter()
+    }
+}
+
+impl<J, R> Drop for Pool<J, R> {
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().closed = true;
+        self.shared.ready.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use glam::IVec3;
+
+/// Turns a seed typed by a person into a world seed. Numbers are taken as is
+/// so they can be shared verbatim, negative ones wrapping around, and
+/// anything else is hashed.
+pub fn parse(text: &str) -> u64 {
+    let text = text.trim();
+
+    text.parse::<u64>()
+        .or_else(|_| text.parse::<i64>().map(|number| number as u64))
+        .unwrap_or_else(|_| seahash::hash(text.as_bytes()))
+}
+
+/// SplitMix64 finalizer, a bijection scrambling every bit of its input.
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+/// Seed for an independent generator derived from the world seed, such as
+/// one noise function. Different `salt`s give different, unrelated seeds.
+pub fn derive(seed: u64, salt: u64) -> u64 {
+    mix(mix(seed) ^ salt)
+}
+
+/// Seed tied to a location, such as that of a chunk or a column. Coordinates
+/// are packed into 21 bits each before mixing, so no two locations within a
+/// million units of the origin share a seed.
+pub fn location(seed: u64, location: IVec3) -> u64 {
+    const MASK: u64 = (1 << 21) - 1;
+
+    let packed = location
+        .to_array()
+        .into_iter()
+        .fold(0, |packed, coord| packed << 21 | (coord as u64 & MASK));
+
+    derive(seed, packed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use glam::ivec3;
+
+    use super::*;
+
+    #[test]
+    fn numbers_are_kept() {
+        assert_eq!(parse("42"), 42);
+        assert_eq!(parse(" 42\n"), 42);
+        assert_eq!(parse("-1"), u64::MAX);
+        assert_eq!(parse("0"), 0);
+
+        // Printed seeds can be typed back in, whatever their size
+        assert_eq!(parse("18446744073709551615"), u64::MAX);
+        assert_eq!(parse(&(1u64 << 63).to_string()), 1 << 63);
+        assert_eq!(parse("-9223372036854775808"), 1 << 63);
+    }
+
+    #[test]
+    fn text_is_hashed() {
+        assert_eq!(parse("ditch"), seahash::hash(b"ditch"));
+        assert_eq!(parse("  ditch "), parse("ditch"));
+        assert_ne!(parse("ditch"), parse("Ditch"));
+
+        // Too large for any integer, so not taken as a number
+        assert_eq!(parse("18446744073709551616"), seahash::hash(b"18446744073709551616"));
+    }
+
+    #[test]
+    fn derived_seeds_differ() {
+        assert_eq!(derive(7, 1), derive(7, 1));
+        assert_ne!(derive(7, 1), derive(7, 2));
+        assert_ne!(derive(7, 1), derive(8, 1));
+        assert_ne!(derive(0, 0), 0);
+    }
+
+    #[test]
+    fn nearby_locations_never_collide() {
+        // Used to collide when chunk seeds were 2x + 3y + 5z
+        assert_ne!(location(0, ivec3(3, 0, 0)), location(0, ivec3(0, 2, 0)));
+
+        let mut seen = HashSet::new();
+
+        for z in -8..8 {
+            for y in -8..8 {
+                for x in -8..8 {
+                    assert!(seen.insert(location(1, ivec3(x, y, z))));
+                }
+            }
+        }
+
+        assert!(seen.insert(location(1, ivec3(1 << 20, 0, 0))));
+        assert_ne!(location(1, IVec3::ZERO), location(2, IVec3::ZERO));
+    }
+}
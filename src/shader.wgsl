@@ -2,6 +2,7 @@ struct PushConstants {
     camera: mat4x4f,
     viewport: vec2f,
     time: f32,
+    cells: u32,
 };
 
 var<push_constant> constants: PushConstants;
@@ -14,6 +15,8 @@ struct V2F {
     @location(0) uv: vec2f,
     @location(1) shadow: f32,
     @location(2) light: u32,
+    // Origin of the atlas cell in xy, size of a cell in z
+    @location(3) @interpolate(flat) cell: vec3f,
 };
 
 @vertex
@@ -22,9 +25,12 @@ fn vertex(
     @location(1) uv: vec2f,
     @location(2) shadow: f32,
     @location(3) light: u32,
+    @location(4) tile: u32,
 ) -> V2F {
     //return v2f;
-    return V2F(constants.camera * vec4f(xyz, 1.0), uv, shadow, light);
+    let size = 1. / f32(constants.cells);
+    let cell = vec3f(f32(tile % constants.cells) * size, f32(tile / constants.cells) * size, size);
+    return V2F(constants.camera * vec4f(xyz, 1.0), uv, shadow, light, cell);
 }
 
 @group(0) @binding(0)
@@ -43,7 +49,9 @@ fn fragment(v: V2F) -> @location(0) vec4f {
 
     let z0 = 2. * v.xyz.z - 1.;
     let z1 = 2. * ZNEAR * ZFAR / (ZFAR + ZNEAR - z0 * (ZFAR - ZNEAR)) / fog;
-    let rgba = textureSample(atlas, samp, v.uv);
+    // Wrap inside the cell, keeping derivatives continuous across repeats
+    let uv = v.cell.xy + fract(v.uv) * v.cell.z;
+    let rgba = textureSampleGrad(atlas, samp, uv, dpdx(v.uv) * v.cell.z, dpdy(v.uv) * v.cell.z);
     if rgba.a == 0.0 { discard; }
     let light = log((E - 1.) * (f32(v.light) + 0.25) / 15.25 + 1.);
     let color = rgba * vec4f(v.shadow, v.shadow, v.shadow, 1.) * light;
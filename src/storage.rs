@@ -0,0 +1,475 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use glam::{ivec3, IVec3};
+
+use crate::{
+    assets::{BlockId, BlockRegistry},
+    chunk::Chunk,
+    types::Cube,
+};
+
+pub const FORMAT_VERSION: u32 = 1;
+
+const LEVEL_MAGIC: [u8; 4] = *b"DTCH";
+const REGION_MAGIC: [u8; 4] = *b"DTRG";
+
+/// Chunks per region file along each axis.
+const REGION_SIZE: i32 = 16;
+const REGION_CHUNKS: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+const HEADER_LEN: u64 = 4 + 4 + 8;
+const TABLE_LEN: u64 = 8 * REGION_CHUNKS as u64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+    pub version: u32,
+    pub seed: u64,
+}
+
+impl Header {
+    fn read(reader: &mut impl Read, magic: [u8; 4]) -> io::Result<Self> {
+        let mut bytes = [0; HEADER_LEN as usize];
+        reader.read_exact(&mut bytes)?;
+
+        if bytes[0..4] != magic {
+            return Err(io::Error::new(ErrorKind::InvalidData, "bad magic"));
+        }
+
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        let seed = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+
+        if version != FORMAT_VERSION {
+            return Err(io::Error::new(ErrorKind::InvalidData, "unsupported format version"));
+        }
+
+        Ok(Self { version, seed })
+    }
+
+    fn write(&self, writer: &mut impl Write, magic: [u8; 4]) -> io::Result<()> {
+        writer.write_all(&magic)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())
+    }
+}
+
+/// A single region file, holding up to 16×16×16 chunks behind an offset
+/// table of `(offset, length)` pairs. A zero length means "not stored".
+struct Region {
+    file: File,
+    table: Box<[(u32, u32); REGION_CHUNKS]>,
+}
+
+impl Region {
+    fn open(path: &Path, header: Header) -> io::Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut table = Box::new([(0, 0); REGION_CHUNKS]);
+
+        if file.metadata()?.len() == 0 {
+            header.write(&mut file, REGION_MAGIC)?;
+            file.write_all(&vec![0; TABLE_LEN as usize])?;
+        } else {
+            let stored = Header::read(&mut file, REGION_MAGIC)?;
+
+            if stored.seed != header.seed {
+                return Err(io::Error::new(ErrorKind::InvalidData, "region belongs to another world"));
+            }
+
+            let mut bytes = vec![0; TABLE_LEN as usize];
+            file.read_exact(&mut bytes)?;
+
+            for (entry, bytes) in table.iter_mut().zip(bytes.chunks_exact(8)) {
+                let offset = u32::from_le_bytes(bytes[0..4].try_into().unwrap());
+                let length = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+                *entry = (offset, length);
+            }
+        }
+
+        Ok(Self { file, table })
+    }
+
+    fn slot(location: IVec3) -> usize {
+        let IVec3 { x, y, z } = location & (REGION_SIZE - 1);
+        (z * REGION_SIZE * REGION_SIZE + y * REGION_SIZE + x) as usize
+    }
+
+    fn read(&mut self, location: IVec3) -> io::Result<Option<Vec<u8>>> {
+        let (offset, length) = self.table[Self::slot(location)];
+
+        if length == 0 {
+            return Ok(None);
+        }
+
+        let mut bytes = vec![0; length as usize];
+        self.file.seek(SeekFrom::Start(offset as u64))?;
+        self.file.read_exact(&mut bytes)?;
+
+        Ok(Some(bytes))
+    }
+
+    fn write(&mut self, location: IVec3, bytes: &[u8]) -> io::Result<()> {
+        let slot = Self::slot(location);
+        let (offset, length) = self.table[slot];
+
+        // Reuse the old spot if the new data fits, append otherwise
+        let offset = if offset != 0 && bytes.len() <= length as usize {
+            offset as u64
+        } else {
+            self.file.seek(SeekFrom::End(0))?
+        };
+
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(bytes)?;
+
+        let entry = (offset as u32, bytes.len() as u32);
+        self.table[slot] = entry;
+
+        self.file.seek(SeekFrom::Start(HEADER_LEN + 8 * slot as u64))?;
+        self.file.write_all(&entry.0.to_le_bytes())?;
+        self.file.write_all(&entry.1.to_le_bytes())
+    }
+}
+
+/// Reads the string following the header of `level.dat`, stored as its
+/// length followed by UTF-8 bytes.
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+
+    let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+    reader.read_exact(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|_| io::Error::new(ErrorKind::InvalidData, "bad string"))
+}
+
+fn write_string(writer: &mut impl Write, string: &str) -> io::Result<()> {
+    writer.write_all(&(string.len() as u32).to_le_bytes())?;
+    writer.write_all(string.as_bytes())
+}
+
+fn write_level(path: &Path, header: Header, generator: &str, names: &[String]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    header.write(&mut file, LEVEL_MAGIC)?;
+    write_string(&mut file, generator)?;
+    file.write_all(&(names.len() as u32).to_le_bytes())?;
+
+    for name in names {
+        write_string(&mut file, name)?;
+    }
+
+    Ok(())
+}
+
+/// On-disk world: a `level.dat` metadata header plus a directory of region
+/// files named after their region coordinates. The header of `level.dat` is
+/// followed by the terrain generator spec and the name of every block ID used
+/// in regions, so they survive packs that number blocks differently.
+pub struct Storage {
+    root: PathBuf,
+    header: Header,
+    generator: String,
+
+    /// Block in the loaded pack for every saved ID, air if the pack lacks it.
+    to_pack: Box<[BlockId]>,
+
+    /// Saved ID of every block in the loaded pack.
+    to_saved: Box<[u16]>,
+
+    regions: HashMap<IVec3, Region>,
+}
+
+impl Storage {
+    /// Opens the world at `path`, creating it with `seed` and the `generator`
+    /// spec if it does not exist yet. The settings of an existing world always
+    /// take precedence. Blocks of `registry` the world has never seen get new
+    /// saved IDs.
+    pub fn open(path: impl AsRef<Path>, seed: u64, generator: &str, registry: &BlockRegistry) -> io::Result<Self> {
+        let root = path.as_ref().to_path_buf();
+        let level_path = root.join("level.dat");
+
+        let (header, generator, mut names, created) = match File::open(&level_path) {
+            Ok(mut file) => {
+                let header = Header::read(&mut file, LEVEL_MAGIC)?;
+                let generator = read_string(&mut file)?;
+
+                let mut len = [0; 4];
+                file.read_exact(&mut len)?;
+
+                let names = (0..u32::from_le_bytes(len))
+                    .map(|_| read_string(&mut file))
+                    .collect::<io::Result<_>>()?;
+
+                (header, generator, names, false)
+            }
+
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let header = Header {
+                    version: FORMAT_VERSION,
+                    seed,
+                };
+
+                fs::create_dir_all(root.join("regions"))?;
+
+                (header, generator.to_string(), Vec::new(), true)
+            }
+
+            Err(err) => return Err(err),
+        };
+
+        let known = names.len();
+
+        let to_saved = registry
+            .iter()
+            .map(|(_, name, _)| match names.iter().position(|saved| saved == name) {
+                Some(idx) => idx as u16,
+
+                None => {
+                    names.push(name.to_string());
+                    names.len() as u16 - 1
+                }
+            })
+            .collect();
+
+        // Saved IDs never change, new blocks are only ever appended
+        if names.len() > known || created {
+            write_level(&level_path, header, &generator, &names)?;
+        }
+
+        let to_pack = names
+            .iter()
+            .map(|name| registry.id(name).unwrap_or(BlockId::AIR))
+            .collect();
+
+        Ok(Self {
+            root,
+            header,
+            generator,
+            to_pack,
+            to_saved,
+            regions: HashMap::default(),
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.header.seed
+    }
+
+    /// Spec of the terrain generator the world was created with.
+    pub fn generator(&self) -> &str {
+        &self.generator
+    }
+
+    fn region(&mut self, location: IVec3) -> io::Result<&mut Region> {
+        let region_loc = location >> 4;
+
+        if !self.regions.contains_key(&region_loc) {
+            let IVec3 { x, y, z } = region_loc;
+            let path = self.root.join("regions").join(format!("r.{x}.{y}.{z}.bin"));
+            let region = Region::open(&path, self.header)?;
+            self.regions.insert(region_loc, region);
+        }
+
+        Ok(self.regions.get_mut(&region_loc).unwrap())
+    }
+
+    pub fn load(&mut self, location: IVec3) -> io::Result<Option<Chunk>> {
+        let Some(bytes) = self.region(location)?.read(location)? else {
+            return Ok(None);
+        };
+
+        decode(&bytes, &self.to_pack)
+            .map(Some)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "corrupted chunk"))
+    }
+
+    pub fn store(&mut self, location: IVec3, chunk: &Chunk) -> io::Result<()> {
+        let bytes = encode(chunk, &self.to_saved);
+        self.region(location)?.write(location, &bytes)
+    }
+}
+
+/// Run-length encodes the chunk contents as `(run, block)` pairs in
+/// z, y, x order, blocks being translated to their saved IDs.
+fn encode(chunk: &Chunk, to_saved: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut run: Option<(u16, u16)> = None;
+
+    for k in 0..32 {
+        for j in 0..32 {
+            for i in 0..32 {
+                let block = to_saved[chunk[ivec3(i, j, k)].0 as usize];
+
+                run = match run {
+                    Some((len, prev)) if prev == block && len < u16::MAX => Some((len + 1, prev)),
+
+                    Some((len, prev)) => {
+                        bytes.extend(len.to_le_bytes());
+                        bytes.extend(prev.to_le_bytes());
+                        Some((1, block))
+                    }
+
+                    None => Some((1, block)),
+                };
+            }
+        }
+    }
+
+    if let Some((len, prev)) = run {
+        bytes.extend(len.to_le_bytes());
+        bytes.extend(prev.to_le_bytes());
+    }
+
+    bytes
+}
+
+fn decode(bytes: &[u8], to_pack: &[BlockId]) -> Option<Chunk> {
+    let mut contents: Box<Cube<BlockId, 32>> = unsafe { Box::new_zeroed().assume_init() };
+    let mut blocks = contents.iter_mut().flatten().flatten();
+
+    for pair in bytes.chunks_exact(4) {
+        let len = u16::from_le_bytes([pair[0], pair[1]]);
+        let block = *to_pack.get(u16::from_le_bytes([pair[2], pair[3]]) as usize)?;
+
+        for _ in 0..len {
+            *blocks.next()? = block;
+        }
+    }
+
+    if blocks.next().is_some() {
+        return None;
+    }
+
+    Some(Chunk::from_contents(contents))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::assets::Block;
+
+    fn registry(names: &[&str]) -> BlockRegistry {
+        BlockRegistry::new(names.iter().map(|name| (name.to_string(), Block::default())))
+    }
+
+    /// Chunk with long runs, single blocks and a run crossing rows and layers.
+    fn sample(registry: &BlockRegistry) -> Chunk {
+        let stone = registry.id("stone").unwrap();
+        let dirt = registry.id("dirt").unwrap();
+        let mut chunk = Chunk::default();
+
+        for k in 0..10 {
+            for j in 0..32 {
+                for i in 0..32 {
+                    chunk.place(ivec3(i, j, k), stone);
+                }
+            }
+        }
+
+        chunk.place(ivec3(31, 31, 10), dirt);
+        chunk.place(ivec3(0, 0, 11), dirt);
+        chunk.place(ivec3(7, 3, 20), dirt);
+        chunk.place(ivec3(8, 3, 20), stone);
+        chunk.place(ivec3(31, 31, 31), stone);
+        chunk
+    }
+
+    fn same(a: &Chunk, b: &Chunk) -> bool {
+        (0..32 * 32 * 32).all(|n| {
+            let location = ivec3(n & 31, n >> 5 & 31, n >> 10);
+            a[location] == b[location]
+        })
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let registry = registry(&["stone", "dirt"]);
+        let identity = registry.iter().map(|(id, ..)| id).collect::<Vec<_>>();
+        let to_saved = identity.iter().map(|id| id.0).collect::<Vec<_>>();
+
+        for chunk in [Chunk::default(), sample(&registry)] {
+            let bytes = encode(&chunk, &to_saved);
+            assert!(same(&decode(&bytes, &identity).unwrap(), &chunk));
+        }
+
+        // A uniform chunk is a single run
+        assert_eq!(encode(&Chunk::default(), &to_saved), [0, 128, 0, 0]);
+    }
+
+    #[test]
+    fn encoding_translates_ids() {
+        let registry = registry(&["stone", "dirt"]);
+        let [air, dirt, stone] = [BlockId::AIR, registry.id("dirt").unwrap(), registry.id("stone").unwrap()];
+
+        // Saved IDs in another order, with a block the pack no longer has
+        let to_saved = [2, 0, 1];
+        let to_pack = [dirt, stone, air, air];
+
+        let chunk = sample(&registry);
+        let decoded = decode(&encode(&chunk, &to_saved), &to_pack).unwrap();
+
+        assert!(same(&decoded, &chunk));
+        assert_eq!(decode(&[0, 128, 3, 0], &to_pack).unwrap()[IVec3::ZERO], air);
+    }
+
+    #[test]
+    fn decoding_rejects_bad_runs() {
+        let to_pack = [BlockId::AIR, BlockId(1)];
+
+        // Too few blocks, too many, and a saved ID out of range
+        assert!(decode(&[0, 127, 0, 0], &to_pack).is_none());
+        assert!(decode(&[0, 128, 0, 0, 1, 0, 1, 0], &to_pack).is_none());
+        assert!(decode(&[0, 128, 2, 0], &to_pack).is_none());
+    }
+
+    #[test]
+    fn regions_round_trip() {
+        let root = std::env::temp_dir().join(format!("ditch-storage-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+
+        let registry = registry(&["stone", "dirt"]);
+        let chunk = sample(&registry);
+        let locations = [ivec3(0, 0, 0), ivec3(-1, 5, -3), ivec3(16, 0, 0)];
+
+        {
+            let mut storage = Storage::open(&root, 7, "flat", &registry).unwrap();
+
+            for location in locations {
+                storage.store(location, &Chunk::default()).unwrap();
+                storage.store(location, &chunk).unwrap();
+            }
+        }
+
+        let mut storage = Storage::open(&root, 8, "void", &registry).unwrap();
+        assert_eq!((storage.seed(), storage.generator()), (7, "flat"));
+
+        for location in locations {
+            assert!(same(&storage.load(location).unwrap().unwrap(), &chunk));
+        }
+
+        assert!(storage.load(ivec3(1, 0, 0)).unwrap().is_none());
+        drop(storage);
+
+        // Blocks keep their names through a pack numbering them differently
+        let renumbered = self::registry(&["sand", "dirt", "stone"]);
+        let mut storage = Storage::open(&root, 7, "flat", &renumbered).unwrap();
+        let loaded = storage.load(locations[0]).unwrap().unwrap();
+
+        for n in 0..32 * 32 * 32 {
+            let location = ivec3(n & 31, n >> 5 & 31, n >> 10);
+            assert_eq!(renumbered.name(loaded[location]), registry.name(chunk[location]));
+        }
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
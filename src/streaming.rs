@@ -0,0 +1,120 @@
+use std::{collections::HashSet, io, ops::RangeInclusive, sync::Arc};
+
+use glam::{IVec3, Vec3};
+
+use crate::{assets::Pack, chunk::Chunk, pool::Pool, world::World};
+
+/// Heights of the chunks to keep loaded around `center`, within bounds and at
+/// most `distance` away.
+fn heights(world: &World, center: IVec3, distance: i32) -> RangeInclusive<i32> {
+    let bounds = world.chunk_bounds();
+    (*bounds.start()).max(center.z - distance)..=(*bounds.end()).min(center.z + distance)
+}
+
+/// Loading order of a chunk, nearest columns first and each one top to bottom
+/// so sky light never has to be taken back.
+fn priority(center: IVec3, location: IVec3) -> i64 {
+    let distance_sq = center.truncate().distance_squared(location.truncate()) as i64;
+    (distance_sq << 32) - location.z as i64
+}
+
+/// Loads whole columns of chunks around the camera on background threads,
+/// nearest first, and unloads those left behind.
+pub struct ChunkStreamer {
+    pool: Pool<IVec3, (IVec3, io::Result<Chunk>)>,
+    pending: HashSet<IVec3>,
+    center: Option<IVec3>,
+
+    /// Columns of chunks closer than this, in chunks, are kept loaded, as far
+    /// up and down as the world goes but no further than this either.
+    distance: i32,
+}
+
+impl ChunkStreamer {
+    pub fn new(world: &World, pack: Arc<Pack>, distance: i32, workers: usize) -> Self {
+        let loader = world.loader(pack);
+
+        Self {
+            pool: Pool::new(workers, move |location| (location, loader(location))),
+            pending: HashSet::default(),
+            center: None,
+            distance,
+        }
+    }
+
+    /// Requests the chunks missing around `position`, unloads distant ones and
+    /// hands at most `budget` finished chunks over to the world.
+    pub fn update(&mut self, world: &mut World, position: Vec3, pack: &Pack, budget: usize) {
+        let center: IVec3 = position.as_ivec3() >> 5;
+        let distance = self.distance;
+
+        if self.center != Some(center) {
+            self.center = Some(center);
+
+            let heights = heights(world, center, distance);
+            let wanted = |location: IVec3| {
+                center.truncate().distance_squared(location.truncate()) < distance * distance && heights.contains(&location.z)
+            };
+
+            let dropped = self.pool.reprioritize(|location| wanted(*location).then(|| priority(center, *location)));
+
+            for location in dropped {
+                self.pending.remove(&location);
+            }
+
+            // Leave some slack so chunks do not thrash at the edge
+            let unload_distance = distance + 2;
+
+            if let Err(err) = world.recenter(center) {
+                eprintln!("failed to save chunks: {err}");
+            }
+
+            let far = world
+                .loaded()
+                .filter(|location| {
+                    center.truncate().distance_squared(location.truncate()) >= unload_distance * unload_distance
+                        || (location.z - center.z).abs() > unload_distance
+                })
+                .collect::<Vec<_>>();
+
+            for location in far {
+                if let Err(err) = world.unload(location) {
+                    eprintln!("failed to save chunk {location}: {err}");
+                }
+            }
+
+            for k in heights.clone() {
+                for j in -distance..=distance {
+                    for i in -distance..=distance {
+                        let location = IVec3::new(center.x + i, center.y + j, k);
+
+                        // The cached area may be narrower than the distance
+                        if !wanted(location) || !world.covers(location) || world.is_loaded(location) || !self.pending.insert(location) {
+                            continue;
+                        }
+
+                        self.pool.submit(priority(center, location), location);
+                    }
+                }
+            }
+        }
+
+        let heights = heights(world, center, distance);
+
+        for (location, chunk) in self.pool.results().take(budget) {
+            self.pending.remove(&location);
+
+            match chunk {
+                Ok(chunk)
+                    if center.truncate().distance_squared(location.truncate()) < distance * distance
+                        && heights.contains(&location.z) =>
+                {
+                    world.insert(location, chunk, pack);
+                }
+
+                Ok(_) => {}
+                Err(err) => eprintln!("failed to load chunk {location}: {err}"),
+            }
+        }
+    }
+}
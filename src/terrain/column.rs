@@ -0,0 +1,53 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use glam::IVec2;
+
+type Entry<T> = Arc<OnceLock<Arc<T>>>;
+
+/// Data shared by every chunk of a column of chunks, such as its height map,
+/// computed once by whichever chunk needs it first. Threads asking for a
+/// column being computed wait for it instead of repeating the work.
+pub struct ColumnCache<T> {
+    /// Columns kept at most, the oldest ones being forgotten first.
+    capacity: usize,
+    entries: Mutex<(HashMap<(u64, IVec2), Entry<T>>, VecDeque<(u64, IVec2)>)>,
+}
+
+impl<T> ColumnCache<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Data of the column at `location` for a world seed, computing it with
+    /// `init` unless cached.
+    pub fn get(&self, seed: u64, location: IVec2, init: impl FnOnce() -> T) -> Arc<T> {
+        let entry = {
+            let (entries, order) = &mut *self.entries.lock().unwrap();
+            let key = (seed, location);
+
+            entries
+                .entry(key)
+                .or_insert_with(|| {
+                    order.push_back(key);
+                    Entry::default()
+                })
+                .clone()
+        };
+
+        let data = entry.get_or_init(|| Arc::new(init())).clone();
+        let (entries, order) = &mut *self.entries.lock().unwrap();
+
+        while order.len() > self.capacity {
+            let oldest = order.pop_front().unwrap();
+            entries.remove(&oldest);
+        }
+
+        data
+    }
+}
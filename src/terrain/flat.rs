@@ -0,0 +1,74 @@
+use glam::ivec3;
+
+use crate::{
+    assets::{BlockId, Pack},
+    types::Cube,
+};
+
+use super::{Context, TerrainGenerator};
+
+/// Nothing but air.
+pub struct Void;
+
+impl TerrainGenerator for Void {
+    fn shape(&self, _context: &mut Context, _blocks: &mut Cube<BlockId, 32>) {}
+}
+
+/// Horizontal layers of blocks stacked from the bottom up, the topmost one
+/// ending right below `z = 0`.
+pub struct Superflat {
+    /// Block of every layer from the bottom up.
+    layers: Vec<BlockId>,
+}
+
+impl Superflat {
+    /// Layers used by plain flat worlds.
+    pub const FLAT: &'static str = "bedrock,60*stone,3*dirt,grass";
+
+    /// Parses a comma separated list of blocks from the bottom up, each one
+    /// optionally prefixed by a count, as in `bedrock,3*dirt,grass`.
+    pub fn parse(spec: &str, pack: &Pack) -> Result<Self, String> {
+        let mut layers = Vec::new();
+
+        for layer in spec.split(',').map(str::trim).filter(|layer| !layer.is_empty()) {
+            let (count, name) = match layer.split_once('*') {
+                Some((count, name)) => {
+                    let count = count
+                        .trim()
+                        .parse::<usize>()
+                        .map_err(|_| format!("bad layer count in `{layer}`"))?;
+
+                    (count, name.trim())
+                }
+
+                None => (1, layer),
+            };
+
+            let block = pack
+                .find_block(name)
+                .ok_or_else(|| format!("unknown block `{name}` in layers"))?;
+
+            layers.extend(std::iter::repeat(block).take(count));
+        }
+
+        Ok(Self { layers })
+    }
+}
+
+impl TerrainGenerator for Superflat {
+    fn shape(&self, context: &mut Context, blocks: &mut Cube<BlockId, 32>) {
+        let bottom = -(self.layers.len() as i32);
+
+        for (k, layer) in blocks.iter_mut().enumerate() {
+            let z = context.world(ivec3(0, 0, k as i32)).z;
+
+            if let Some(block) = usize::try_from(z - bottom).ok().and_then(|idx| self.layers.get(idx)) {
+                layer.iter_mut().flatten().for_each(|cell| *cell = *block);
+            }
+        }
+
+        if !self.layers.is_empty() {
+            context.heights = [[-1; 32]; 32];
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use glam::{DVec3, IVec3};
+use noise::NoiseFn;
+
+/// Blocks between two lattice points along each axis.
+const STEP: i32 = 4;
+
+/// Noise sampled on a coarse lattice and interpolated in between, which is
+/// far cheaper than sampling every block. Values only depend on the world
+/// location, so lattices covering different areas agree where they overlap.
+pub struct Lattice {
+    /// First lattice point, in lattice units.
+    min: IVec3,
+    size: IVec3,
+    values: Box<[f64]>,
+}
+
+impl Lattice {
+    /// Covers every block from `min` to `max`, both included. Features of the
+    /// noise are `scale` blocks across.
+    pub fn new(noise: &impl NoiseFn<f64, 3>, scale: f64, min: IVec3, max: IVec3) -> Self {
+        let min = min.div_euclid(IVec3::splat(STEP));
+        let size = max.div_euclid(IVec3::splat(STEP)) - min + IVec3::splat(2);
+        let mut values = Vec::with_capacity((size.x * size.y * size.z) as usize);
+
+        for k in 0..size.z {
+            for j in 0..size.y {
+                for i in 0..size.x {
+                    let point = (min + IVec3::new(i, j, k)) * STEP;
+                    values.push(noise.get((point.as_dvec3() / scale).to_array()));
+                }
+            }
+        }
+
+        Self {
+            min,
+            size,
+            values: values.into_boxed_slice(),
+        }
+    }
+
+    fn value(&self, point: IVec3) -> f64 {
+        let IVec3 { x, y, z } = point - self.min;
+        self.values[((z * self.size.y + y) * self.size.x + x) as usize]
+    }
+
+    /// Interpolated value at a location covered by the lattice.
+    pub fn get(&self, location: IVec3) -> f64 {
+        let base = location.div_euclid(IVec3::splat(STEP));
+        let t = location.rem_euclid(IVec3::splat(STEP)).as_dvec3() / STEP as f64;
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+        let corner = |dx, dy, dz| self.value(base + IVec3::new(dx, dy, dz));
+        let DVec3 { x, y, z } = t;
+
+        let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), x);
+        let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), x);
+        let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), x);
+        let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), x);
+
+        lerp(lerp(x00, x10, y), lerp(x01, x11, y), z)
+    }
+}
@@ -0,0 +1,162 @@
+use std::{collections::HashMap, sync::Arc};
+
+use glam::IVec3;
+use rand_xoshiro::{rand_core::SeedableRng, Xoshiro256PlusPlus};
+
+use crate::{
+    assets::{BlockId, Pack},
+    chunk::Chunk,
+    seed,
+    types::Cube,
+};
+
+pub use self::{
+    flat::{Superflat, Void},
+    perlin::Perlin,
+};
+
+mod column;
+mod flat;
+mod lattice;
+mod perlin;
+
+/// Everything a generator knows about the chunk being generated.
+pub struct Context<'p> {
+    pub location: IVec3,
+    pub seed: u64,
+    pub pack: &'p Pack,
+
+    /// Random numbers private to this chunk, so generation stays reproducible.
+    pub rand: Xoshiro256PlusPlus,
+
+    /// Surface height of every column in world coordinates, indexed by y and
+    /// then x. Generators without a surface leave it at `i32::MIN`.
+    pub heights: [[i32; 32]; 32],
+
+    /// Biome of every column, indexed like `heights`, as an index into the
+    /// biomes known to the generator.
+    pub biomes: [[usize; 32]; 32],
+}
+
+impl Context<'_> {
+    /// World location of a block inside the chunk.
+    pub fn world(&self, block_loc: IVec3) -> IVec3 {
+        self.location * 32 + block_loc
+    }
+}
+
+/// Produces the contents of chunks in stages, each one working over what the
+/// previous ones left. Only `shape` is mandatory.
+pub trait TerrainGenerator: Send + Sync {
+    /// Lays down the bulk of the terrain, usually a single material.
+    fn shape(&self, context: &mut Context, blocks: &mut Cube<BlockId, 32>);
+
+    /// Swaps the top layers for soil, sand and the like.
+    fn surface(&self, _context: &mut Context, _blocks: &mut Cube<BlockId, 32>) {}
+
+    /// Digs caves and ravines out of the terrain.
+    fn carve(&self, _context: &mut Context, _blocks: &mut Cube<BlockId, 32>) {}
+
+    /// Adds small features such as plants and ores.
+    fn decorate(&self, _context: &mut Context, _blocks: &mut Cube<BlockId, 32>) {}
+}
+
+/// Runs every stage of `generator` for the chunk at `location`. The same seed
+/// always yields the same contents.
+pub fn generate(generator: &dyn TerrainGenerator, location: IVec3, world_seed: u64, pack: &Pack) -> Chunk {
+    let mut blocks: Box<Cube<BlockId, 32>> = unsafe { Box::new_zeroed().assume_init() };
+
+    let mut context = Context {
+        location,
+        seed: world_seed,
+        pack,
+        rand: Xoshiro256PlusPlus::seed_from_u64(seed::location(world_seed, location)),
+        heights: [[i32::MIN; 32]; 32],
+        biomes: [[0; 32]; 32],
+    };
+
+    generator.shape(&mut context, &mut blocks);
+    generator.surface(&mut context, &mut blocks);
+    generator.carve(&mut context, &mut blocks);
+    generator.decorate(&mut context, &mut blocks);
+
+    Chunk::from_contents(blocks)
+}
+
+/// Builds a generator from the options following its name in a spec.
+pub type Constructor = fn(options: &str, pack: &Pack) -> Result<Arc<dyn TerrainGenerator>, String>;
+
+/// Generators selectable by name. Specs are a name optionally followed by a
+/// colon and options, such as `superflat:bedrock,3*dirt,grass`.
+pub struct Registry {
+    constructors: HashMap<String, Constructor>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Self {
+            constructors: HashMap::default(),
+        };
+
+        registry.register("default", |_, pack| Ok(Arc::new(Perlin::new(pack)?)));
+        registry.register("void", |_, _| Ok(Arc::new(Void)));
+        registry.register("flat", |_, pack| Ok(Arc::new(Superflat::parse(Superflat::FLAT, pack)?)));
+        registry.register("superflat", |layers, pack| Ok(Arc::new(Superflat::parse(layers, pack)?)));
+
+        registry
+    }
+}
+
+impl Registry {
+    /// Makes a generator available under `name`, replacing any other.
+    pub fn register(&mut self, name: impl Into<String>, constructor: Constructor) {
+        self.constructors.insert(name.into(), constructor);
+    }
+
+    /// Names of every generator, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self.constructors.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    pub fn create(&self, spec: &str, pack: &Pack) -> Result<Arc<dyn TerrainGenerator>, String> {
+        let (name, options) = spec.split_once(':').unwrap_or((spec, ""));
+
+        let constructor = self
+            .constructors
+            .get(name)
+            .ok_or_else(|| format!("unknown terrain generator `{name}`"))?;
+
+        constructor(options, pack)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::ivec3;
+
+    use super::*;
+    use crate::assets;
+
+    #[test]
+    fn same_seed_same_chunks() {
+        let pack = assets::open("pack").unwrap();
+
+        // Separately built, so nothing cached by the first carries over
+        let first = Perlin::new(&pack).unwrap();
+        let second = Perlin::new(&pack).unwrap();
+
+        for location in [ivec3(0, 0, 0), ivec3(0, 0, 1), ivec3(0, 0, 2), ivec3(-3, 5, 1), ivec3(7, -2, -1)] {
+            for seed in [0, 42, u64::MAX] {
+                let a = generate(&first, location, seed, &pack);
+                let b = generate(&second, location, seed, &pack);
+
+                for n in 0..32 * 32 * 32 {
+                    let block_loc = ivec3(n & 31, n >> 5 & 31, n >> 10);
+                    assert_eq!(a[block_loc], b[block_loc], "chunk {location} of seed {seed} differs at {block_loc}");
+                }
+            }
+        }
+    }
+}
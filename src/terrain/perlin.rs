@@ -0,0 +1,614 @@
+use std::{f64::consts::SQRT_2, sync::Arc};
+
+use glam::{ivec2, ivec3, IVec2, IVec3};
+use noise::NoiseFn;
+use rand_xoshiro::{
+    rand_core::{RngCore, SeedableRng},
+    Xoshiro256PlusPlus,
+};
+
+use crate::{
+    assets::{Biome, BlockId, Density, Pack, Worldgen},
+    seed,
+    types::{Cube, DIRECTIONS},
+};
+
+use super::{column::ColumnCache, lattice::Lattice, Context, TerrainGenerator};
+
+/// Size of climate features, in blocks.
+const CLIMATE_SCALE: f64 = 512.;
+
+/// How far apart in climate two biomes still blend their heights. Larger
+/// values give smoother but blander transitions.
+const BLEND: f64 = 0.02;
+
+/// Salts deriving every noise function from the world seed.
+const HEIGHT_SALTS: [u64; 3] = [1, 2, 3];
+const TEMPERATURE_SALT: u64 = 4;
+const HUMIDITY_SALT: u64 = 5;
+const DECORATION_SALT: u64 = 6;
+const STRUCTURE_SALT: u64 = 7;
+const DENSITY_SALT: u64 = 8;
+const CHAMBER_SALT: u64 = 9;
+const TUNNEL_SALTS: [u64; 2] = [10, 11];
+const ORE_SALT: u64 = 12;
+
+/// Columns of chunks whose height maps are kept around. Streaming keeps
+/// asking for the same few hundred around the player.
+const CACHED_COLUMNS: usize = 1024;
+
+/// Sea floors down to this far below the sea are covered in beach.
+const SHALLOWS: i32 = 4;
+
+/// Maps a random number to `[0, 1)`.
+fn unit(roll: u64) -> f64 {
+    (roll >> 11) as f64 / (1u64 << 53) as f64
+}
+
+struct Noises {
+    height: [noise::Perlin; 3],
+    temperature: noise::Perlin,
+    humidity: noise::Perlin,
+    density: noise::Perlin,
+    chamber: noise::Perlin,
+    tunnel: [noise::Perlin; 2],
+}
+
+impl Noises {
+    fn new(world_seed: u64) -> Self {
+        let perlin = |salt| noise::Perlin::new(seed::derive(world_seed, salt) as u32);
+
+        Self {
+            height: HEIGHT_SALTS.map(perlin),
+            temperature: perlin(TEMPERATURE_SALT),
+            humidity: perlin(HUMIDITY_SALT),
+            density: perlin(DENSITY_SALT),
+            chamber: perlin(CHAMBER_SALT),
+            tunnel: TUNNEL_SALTS.map(perlin),
+        }
+    }
+
+    /// Roughly from -1 to 1, three octaves of hills.
+    fn height(&self, x: i32, y: i32) -> f64 {
+        let fx = x as f64;
+        let fy = y as f64;
+        let factor = SQRT_2 / 1000.;
+        let val1 = self.height[0].get([fx * factor, fy * factor]) * 0.9;
+        let val2 = self.height[1].get([fx * 10. * factor, fy * 10. * factor]) * 0.09;
+        let val3 = self.height[2].get([fx * 100. * factor, fy * 100. * factor]) * 0.009;
+
+        val1 + val2 + val3
+    }
+
+    fn climate(&self, x: i32, y: i32) -> (f64, f64) {
+        let point = [x as f64 / CLIMATE_SCALE, y as f64 / CLIMATE_SCALE];
+        (self.temperature.get(point), self.humidity.get(point))
+    }
+}
+
+/// Height map of a column of chunks.
+struct Column {
+    /// Height of every column before density bends it, indexed by y and then x.
+    heights: [[i32; 32]; 32],
+
+    /// Highest ground of every column.
+    tops: [[i32; 32]; 32],
+
+    biomes: [[usize; 32]; 32],
+
+    /// Density noise around the heights, if the terrain uses it.
+    density: Option<Lattice>,
+}
+
+/// Sea settings with their blocks looked up.
+struct Water {
+    level: i32,
+    block: BlockId,
+    beach: BlockId,
+    beach_height: i32,
+}
+
+/// Rolling hills made of three octaves of Perlin noise over stone, dressed up
+/// by the biome each column falls into.
+pub struct Perlin {
+    bedrock: BlockId,
+    cobblestone: BlockId,
+    stone: BlockId,
+    biomes: Box<[Biome]>,
+    worldgen: Worldgen,
+    sea: Option<Water>,
+    columns: ColumnCache<Column>,
+}
+
+impl Perlin {
+    pub fn new(pack: &Pack) -> Result<Self, String> {
+        let block = |name: &str| pack.find_block(name).ok_or_else(|| format!("missing block `{name}`"));
+
+        let mut biomes = pack
+            .biomes
+            .iter()
+            .map(|(_, biome)| biome.clone())
+            .collect::<Box<_>>();
+
+        // Packs without biomes get plain grassland everywhere
+        if biomes.is_empty() {
+            biomes = Box::new([Biome {
+                temperature: 0.,
+                humidity: 0.,
+                surface: block("grass")?,
+                filler: block("dirt")?,
+                filler_depth: 4,
+                height: 0.,
+                variation: 30.,
+                decorations: Box::new([]),
+                structures: Box::new([]),
+            }]);
+        }
+
+        let sea = match &pack.worldgen.sea {
+            Some(sea) => Some(Water {
+                level: sea.level,
+                block: block(&sea.block)?,
+                beach: block(&sea.beach)?,
+                beach_height: sea.beach_height,
+            }),
+
+            None => None,
+        };
+
+        Ok(Self {
+            bedrock: block("bedrock")?,
+            cobblestone: block("cobblestone")?,
+            stone: block("stone")?,
+            biomes,
+            worldgen: pack.worldgen.clone(),
+            sea,
+            columns: ColumnCache::new(CACHED_COLUMNS),
+        })
+    }
+
+    /// Surface height of a column, blended across the biomes close in climate,
+    /// and the biome closest of all.
+    fn sample(&self, noises: &Noises, x: i32, y: i32) -> (i32, usize) {
+        let (temperature, humidity) = noises.climate(x, y);
+        let val = noises.height(x, y);
+
+        let mut total = 0.;
+        let mut height = 0.;
+        let mut closest = (0, f64::INFINITY);
+
+        for (idx, biome) in self.biomes.iter().enumerate() {
+            let distance_sq = (biome.temperature - temperature).powi(2) + (biome.humidity - humidity).powi(2);
+            let weight = (-distance_sq / BLEND).exp();
+
+            total += weight;
+            height += weight * (biome.height + biome.variation * val);
+
+            if distance_sq < closest.1 {
+                closest = (idx, distance_sq);
+            }
+        }
+
+        // Far from every biome all weights vanish, so just take the closest
+        let height = if total > f64::EPSILON {
+            height / total
+        } else {
+            let biome = &self.biomes[closest.0];
+            biome.height + biome.variation * val
+        };
+
+        (height as i32, closest.0)
+    }
+
+    /// Whether a column with its ground at `top` lies under the sea.
+    fn flooded(&self, top: i32) -> bool {
+        self.sea.as_ref().is_some_and(|sea| top < sea.level)
+    }
+
+    /// Density noise around the surface of the columns between two corners,
+    /// or nothing when the terrain is a plain height map.
+    fn density(&self, noises: &Noises, min: IVec3, max: IVec3) -> Option<Lattice> {
+        let Density { strength, scale } = self.worldgen.density;
+        let reach = IVec3::Z * strength.ceil() as i32;
+
+        (strength > 0.).then(|| Lattice::new(&noises.density, scale, min - reach, max + reach))
+    }
+
+    /// Whether there is ground at a location, given the height of its column.
+    /// Density only matters close to the height, so the lattice need not cover
+    /// anything else.
+    fn solid(&self, density: Option<&Lattice>, location: IVec3, height: i32) -> bool {
+        let strength = self.worldgen.density.strength;
+
+        match density {
+            Some(density) if ((location.z - height).abs() as f64) <= strength => {
+                location.z as f64 <= height as f64 + strength * density.get(location)
+            }
+
+            _ => location.z <= height,
+        }
+    }
+
+    /// Highest ground in a column of the given height.
+    fn top(&self, density: Option<&Lattice>, x: i32, y: i32, height: i32) -> i32 {
+        let reach = self.worldgen.density.strength.ceil() as i32;
+
+        (height - reach..=height + reach)
+            .rev()
+            .find(|z| self.solid(density, ivec3(x, y, *z), height))
+            .unwrap_or(height - reach - 1)
+    }
+
+    /// Everything about the column of chunks at `location` that does not
+    /// depend on height, computed once for all of its chunks.
+    fn column(&self, world_seed: u64, location: IVec2) -> Arc<Column> {
+        self.columns.get(world_seed, location, || {
+            let noises = Noises::new(world_seed);
+            let min = location * 32;
+            let mut heights = [[0; 32]; 32];
+            let mut tops = [[0; 32]; 32];
+            let mut biomes = [[0; 32]; 32];
+
+            for j in 0..32 {
+                for i in 0..32 {
+                    let IVec2 { x, y } = min + ivec2(i as i32, j as i32);
+                    (heights[j][i], biomes[j][i]) = self.sample(&noises, x, y);
+                }
+            }
+
+            let low = heights.iter().flatten().min().unwrap();
+            let high = heights.iter().flatten().max().unwrap();
+            let density = self.density(&noises, min.extend(*low), (min + 31).extend(*high));
+
+            for j in 0..32 {
+                for i in 0..32 {
+                    let IVec2 { x, y } = min + ivec2(i as i32, j as i32);
+                    tops[j][i] = self.top(density.as_ref(), x, y, heights[j][i]);
+                }
+            }
+
+            Column {
+                heights,
+                tops,
+                biomes,
+                density,
+            }
+        })
+    }
+
+    /// Highest ground of a single column and its biome.
+    fn ground(&self, world_seed: u64, x: i32, y: i32) -> (i32, usize) {
+        let location = ivec2(x, y);
+        let column = self.column(world_seed, location.div_euclid(IVec2::splat(32)));
+        let IVec2 { x: i, y: j } = location.rem_euclid(IVec2::splat(32));
+
+        (column.tops[j as usize][i as usize], column.biomes[j as usize][i as usize])
+    }
+
+    /// Writes the part inside this chunk of every structure that reaches into
+    /// it. Spots are scattered over a grid with one candidate per cell and only
+    /// depend on the seed, so neighbouring chunks agree on them whichever
+    /// generates first.
+    fn place_structures(&self, context: &Context, noises: &Noises, blocks: &mut Cube<BlockId, 32>) {
+        let seed = seed::derive(context.seed, STRUCTURE_SALT);
+        let min = context.location * 32;
+        let max = min + IVec3::splat(31);
+
+        for (idx, (_, structure)) in context.pack.structures.iter().enumerate() {
+            let seed = seed::derive(seed, idx as u64);
+            let spacing = structure.spacing;
+            let first = (min.truncate() - structure.reach).div_euclid(IVec2::splat(spacing));
+            let last = (max.truncate() + structure.reach).div_euclid(IVec2::splat(spacing));
+
+            for cell_y in first.y..=last.y {
+                for cell_x in first.x..=last.x {
+                    let roll = seed::location(seed, ivec3(cell_x, cell_y, 0));
+                    let x = cell_x * spacing + (roll % spacing as u64) as i32;
+                    let y = cell_y * spacing + ((roll >> 32) % spacing as u64) as i32;
+                    let (h, biome) = self.ground(context.seed, x, y);
+
+                    if self.flooded(h) || !self.standing(noises, ivec3(x, y, h)) {
+                        continue;
+                    }
+
+                    let chance = self.biomes[biome]
+                        .structures
+                        .iter()
+                        .find(|placement| placement.structure == idx)
+                        .map_or(0., |placement| placement.chance);
+
+                    if unit(seed::derive(roll, 0)) >= chance {
+                        continue;
+                    }
+
+                    for (offset, block) in structure.blocks.iter() {
+                        let location = ivec3(x, y, h) + *offset;
+
+                        if location.cmpge(min).all() && location.cmple(max).all() && location.z < self.ceiling() {
+                            let IVec3 { x, y, z } = location - min;
+                            blocks[z as usize][y as usize][x as usize] = *block;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Writes the part inside this chunk of every ore vein reaching into it.
+    /// Veins start at random spots of every 32 by 32 by 32 volume and wander
+    /// one block at a time, so like structures they may cross chunk borders.
+    fn place_ores(&self, context: &Context, blocks: &mut Cube<BlockId, 32>) {
+        let seed = seed::derive(context.seed, ORE_SALT);
+        let min = context.location * 32;
+        let max = min + IVec3::splat(31);
+
+        for (idx, (_, ore)) in context.pack.ores.iter().enumerate() {
+            let seed = seed::derive(seed, idx as u64);
+            let reach = IVec3::splat(ore.size as i32);
+            let first = (min - reach).max(ivec3(i32::MIN, i32::MIN, ore.min_height)).div_euclid(IVec3::splat(32));
+            let last = (max + reach).min(ivec3(i32::MAX, i32::MAX, ore.max_height)).div_euclid(IVec3::splat(32));
+
+            for cell_z in first.z..=last.z {
+                for cell_y in first.y..=last.y {
+                    for cell_x in first.x..=last.x {
+                        let cell = ivec3(cell_x, cell_y, cell_z);
+                        let mut rand = Xoshiro256PlusPlus::seed_from_u64(seed::location(seed, cell));
+                        let extra = unit(rand.next_u64()) < ore.frequency.fract();
+                        let veins = ore.frequency as u32 + extra as u32;
+
+                        for _ in 0..veins {
+                            let roll = rand.next_u64();
+                            let offset = ivec3(roll as i32, (roll >> 21) as i32, (roll >> 42) as i32) & 31;
+                            let mut location = cell * 32 + offset;
+
+                            if !(ore.min_height..=ore.max_height).contains(&location.z) {
+                                continue;
+                            }
+
+                            for _ in 0..ore.size {
+                                if location.cmpge(min).all() && location.cmple(max).all() {
+                                    let IVec3 { x, y, z } = location - min;
+                                    let block = &mut blocks[z as usize][y as usize][x as usize];
+
+                                    if ore.replaces.contains(block) {
+                                        *block = ore.block;
+                                    }
+                                }
+
+                                let step = DIRECTIONS[rand.next_u32() as usize % DIRECTIONS.len()];
+                                location += IVec3::from(step);
+                                location.z = location.z.clamp(ore.min_height, ore.max_height);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lowest layer of the world, made of unbreakable bedrock.
+    fn floor(&self) -> i32 {
+        self.worldgen.height.bottom
+    }
+
+    /// Nothing generates at or above this height.
+    fn ceiling(&self) -> i32 {
+        self.worldgen.height.top
+    }
+
+    /// Whether caves dig out the block at `location`, whose column has its
+    /// ground at `top`. Blocks that are never dug, such as bedrock, are up to
+    /// the caller.
+    fn carved(&self, chambers: &Lattice, tunnels: &[Lattice; 2], location: IVec3, top: i32) -> bool {
+        let caves = self.worldgen.caves;
+
+        if location.z <= self.floor() + caves.floor_margin {
+            return false;
+        }
+
+        let covered = location.z < top - caves.cover;
+        let chamber = covered && chambers.get(location) > caves.chamber_threshold;
+
+        // Tunnels opening under the sea would leave walls of water around
+        let tunnel = (covered || self.sea.as_ref().map_or(true, |sea| location.z > sea.level))
+            && tunnels
+                .iter()
+                .all(|tunnel| tunnel.get(location).abs() < caves.tunnel_width);
+
+        chamber || tunnel
+    }
+
+    /// Whether the ground at `location`, the top of its column, is left after
+    /// carving for something to stand on. Only depends on the location, so
+    /// chunks agree on it whichever one holds the ground.
+    fn standing(&self, noises: &Noises, location: IVec3) -> bool {
+        let caves = self.worldgen.caves;
+        let chambers = Lattice::new(&noises.chamber, caves.chamber_scale, location, location);
+        let tunnels = noises
+            .tunnel
+            .each_ref()
+            .map(|noise| Lattice::new(noise, caves.tunnel_scale, location, location));
+
+        !self.carved(&chambers, &tunnels, location, location.z)
+    }
+
+    /// Calls `f` for every block of the chunk between the floor and ceiling,
+    /// along with its world location.
+    fn for_each(&self, chunk_loc: IVec3, mut f: impl FnMut(IVec3, IVec3)) {
+        for k in 0..32 {
+            for j in 0..32 {
+                for i in 0..32 {
+                    let block_loc = ivec3(i, j, k);
+                    let location = chunk_loc * 32 + block_loc;
+
+                    if (self.floor()..self.ceiling()).contains(&location.z) {
+                        f(block_loc, location);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl TerrainGenerator for Perlin {
+    fn shape(&self, context: &mut Context, blocks: &mut Cube<BlockId, 32>) {
+        let column = self.column(context.seed, context.location.truncate());
+        let Column { heights, density, .. } = &*column;
+
+        context.heights = column.tops;
+        context.biomes = column.biomes;
+
+        let rand = &mut context.rand;
+
+        self.for_each(context.location, |IVec3 { x, y, z }, location| {
+            let block = &mut blocks[z as usize][y as usize][x as usize];
+            let h = heights[y as usize][x as usize];
+            let rand = rand.next_u32() as usize;
+
+            *block = if location.z == self.floor() {
+                self.bedrock
+            } else if location.z == self.floor() + 1 {
+                [self.stone, self.cobblestone, self.bedrock, self.bedrock][rand % 4]
+            } else if self.solid(density.as_ref(), location, h) {
+                [self.stone, self.cobblestone][(rand >> 2) % 2]
+            } else {
+                match &self.sea {
+                    Some(sea) if location.z <= sea.level => sea.block,
+                    _ => BlockId::AIR,
+                }
+            };
+        });
+    }
+
+    fn surface(&self, context: &mut Context, blocks: &mut Cube<BlockId, 32>) {
+        let min = context.world(IVec3::ZERO);
+
+        for j in 0..32 {
+            for i in 0..32 {
+                let top = context.heights[j][i];
+                let biome = &self.biomes[context.biomes[j][i]];
+                let filler = biome.filler_depth;
+                let water = self.sea.as_ref().map(|sea| sea.block);
+
+                let (surface, filler_block) = match &self.sea {
+                    Some(sea) if top > sea.level + sea.beach_height => (biome.surface, biome.filler),
+                    Some(sea) if top >= sea.level - SHALLOWS => (sea.beach, sea.beach),
+
+                    // Deep sea floors have nothing growing on them
+                    Some(_) => (biome.filler, biome.filler),
+                    None => (biome.surface, biome.filler),
+                };
+
+                // Blocks above the chunk are taken to be solid up to the top
+                let mut depth = (top - (min.z + 31)).max(0);
+
+                for k in (0..32).rev() {
+                    let z = min.z + k as i32;
+                    let block = &mut blocks[k][j][i];
+
+                    if *block == BlockId::AIR || Some(*block) == water {
+                        depth = 0;
+                        continue;
+                    }
+
+                    if z > self.floor() + 1 && z < self.ceiling() {
+                        // Filler thins out into the stone below
+                        if depth == 0 {
+                            *block = surface;
+                        } else if depth <= filler {
+                            *block = filler_block;
+                        } else if depth <= filler + 5 {
+                            let rand = context.rand.next_u32() as usize;
+                            *block = [filler_block, filler_block, self.stone, self.cobblestone][(rand >> 3) % 4];
+                        }
+                    }
+
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    fn carve(&self, context: &mut Context, blocks: &mut Cube<BlockId, 32>) {
+        let caves = self.worldgen.caves;
+        let min = context.world(IVec3::ZERO);
+        let max = min + IVec3::splat(31);
+        let top = *context.heights.iter().flatten().max().unwrap();
+
+        if max.z <= self.floor() + caves.floor_margin || min.z > top {
+            return;
+        }
+
+        let noises = Noises::new(context.seed);
+        let chambers = Lattice::new(&noises.chamber, caves.chamber_scale, min, max);
+        let tunnels = noises
+            .tunnel
+            .each_ref()
+            .map(|noise| Lattice::new(noise, caves.tunnel_scale, min, max));
+
+        self.for_each(context.location, |block_loc, location| {
+            let IVec3 { x, y, z } = block_loc;
+            let block = &mut blocks[z as usize][y as usize][x as usize];
+
+            let water = self.sea.as_ref().map(|sea| sea.block);
+
+            if *block == BlockId::AIR || *block == self.bedrock || Some(*block) == water {
+                return;
+            }
+
+            if self.carved(&chambers, &tunnels, location, context.heights[y as usize][x as usize]) {
+                *block = BlockId::AIR;
+            }
+        });
+    }
+
+    fn decorate(&self, context: &mut Context, blocks: &mut Cube<BlockId, 32>) {
+        let seed = seed::derive(context.seed, DECORATION_SALT);
+        let noises = Noises::new(context.seed);
+
+        for j in 0..32 {
+            for i in 0..32 {
+                let location = context.world(ivec3(i as i32, j as i32, 0));
+                let h = context.heights[j][i];
+                let biome = &self.biomes[context.biomes[j][i]];
+
+                if self.flooded(h) {
+                    continue;
+                }
+
+                // Rolls only depend on the column, so the chunk above places the
+                // same decoration when it sticks out of this one
+                let mut roll = seed::location(seed, location.truncate().extend(0));
+
+                for decoration in biome.decorations.iter() {
+                    roll = seed::derive(roll, 0);
+
+                    if unit(roll) >= decoration.chance {
+                        continue;
+                    }
+
+                    // Tunnels opening at the surface may have taken the ground
+                    if !self.standing(&noises, location.truncate().extend(h)) {
+                        break;
+                    }
+
+                    let span = decoration.max_height - decoration.min_height + 1;
+                    let height = decoration.min_height + (roll % span as u64) as u32;
+
+                    for z in (h + 1..=h + height as i32).filter(|z| *z < self.ceiling()) {
+                        let k = z - context.location.z * 32;
+
+                        if (0..32).contains(&k) {
+                            blocks[k as usize][j][i] = decoration.block;
+                        }
+                    }
+
+                    break;
+                }
+            }
+        }
+
+        self.place_ores(context, blocks);
+        self.place_structures(context, &noises, blocks);
+    }
+}
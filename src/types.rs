@@ -1,10 +1,15 @@
 use std::{
+    fmt,
+    marker::PhantomData,
     mem,
     ops::{Index, IndexMut},
 };
 
 use glam::{IVec3, Vec3};
-use serde::Deserialize;
+use serde::{
+    de::{self, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
 
 pub const DIRECTIONS: [Direction; mem::variant_count::<Direction>()] = [
     Direction::West,
@@ -30,7 +35,7 @@ pub type Layer<T, const N: usize> = [[T; N]; N];
 pub type Cube<T, const N: usize> = [Layer<T, N>; N];
 
 #[repr(u8)]
-#[derive(Copy, Clone, Debug, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Direction {
     West,
@@ -42,6 +47,15 @@ pub enum Direction {
 }
 
 impl Direction {
+    /// Index of the axis the direction points along, x being 0.
+    pub fn axis(self) -> usize {
+        match self {
+            Direction::West | Direction::East => 0,
+            Direction::South | Direction::North => 1,
+            Direction::Down | Direction::Up => 2,
+        }
+    }
+
     pub fn opposite(self) -> Self {
         match self {
             Direction::West => Direction::East,
@@ -80,25 +94,17 @@ impl From<Direction> for Vec3 {
     }
 }
 
+/// One value for each direction. When deserialized, values may be shared
+/// through the `x`, `y` and `z` axes, the four horizontal `sides` or `all` of
+/// them, the most specific key winning.
 #[repr(C)]
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default)]
 pub struct DirMap<T> {
-    // #[serde(fallback = "x", fallback = "all")]
     pub west: T,
-
-    // #[serde(fallback = "x", fallback = "all")]
     pub east: T,
-
-    // #[serde(fallback = "y", fallback = "all")]
     pub south: T,
-
-    // #[serde(fallback = "y", fallback = "all")]
     pub north: T,
-
-    // #[serde(fallback = "z", fallback = "all")]
     pub down: T,
-
-    // #[serde(fallback = "z", fallback = "all")]
     pub up: T,
 }
 
@@ -143,27 +149,16 @@ impl<T> IndexMut<Direction> for DirMap<T> {
     }
 }
 
+/// Like [`DirMap`], plus a value for whatever faces no direction.
 #[repr(C)]
-#[derive(Clone, Debug, Default, Deserialize)]
+#[derive(Clone, Debug, Default)]
 pub struct SideMap<T> {
-    // #[serde(fallback = "x", fallback = "all")]
     pub west: T,
-
-    // #[serde(fallback = "x", fallback = "all")]
     pub east: T,
-
-    // #[serde(fallback = "y", fallback = "all")]
     pub south: T,
-
-    // #[serde(fallback = "y", fallback = "all")]
     pub north: T,
-
-    // #[serde(fallback = "z", fallback = "all")]
     pub down: T,
-
-    // #[serde(fallback = "z", fallback = "all")]
     pub up: T,
-
     pub none: T,
 }
 
@@ -210,3 +205,146 @@ impl<T> IndexMut<Side> for SideMap<T> {
         }
     }
 }
+
+const DIR_KEYS: &[&str] = &["west", "east", "south", "north", "down", "up", "x", "y", "z", "sides", "all"];
+const SIDE_KEYS: &[&str] = &["west", "east", "south", "north", "down", "up", "none", "x", "y", "z", "sides", "all"];
+
+/// Keys a value of each direction may come from, most specific first.
+fn fallbacks(side: Side) -> &'static [&'static str] {
+    match side {
+        Some(Direction::West) => &["west", "x", "sides", "all"],
+        Some(Direction::East) => &["east", "x", "sides", "all"],
+        Some(Direction::South) => &["south", "y", "sides", "all"],
+        Some(Direction::North) => &["north", "y", "sides", "all"],
+        Some(Direction::Down) => &["down", "z", "all"],
+        Some(Direction::Up) => &["up", "z", "all"],
+        None => &["none", "all"],
+    }
+}
+
+/// Reads the entries of a map keyed by direction or shorthand, rejecting
+/// unknown and repeated keys.
+struct Shorthand<T> {
+    keys: &'static [&'static str],
+    value: PhantomData<fn() -> T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for Shorthand<T> {
+    type Value = Vec<(String, T)>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a map with keys among {}", self.keys.join(", "))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = Vec::<(String, T)>::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            if !self.keys.contains(&key.as_str()) {
+                return Err(de::Error::unknown_field(&key, self.keys));
+            }
+
+            if entries.iter().any(|(other, _)| *other == key) {
+                return Err(de::Error::custom(format_args!("duplicate field `{key}`")));
+            }
+
+            let value = map.next_value()?;
+            entries.push((key, value));
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Reads a map of faces, returning what each side ends up with.
+fn expand<'de, D, T>(deserializer: D, keys: &'static [&'static str]) -> Result<impl Fn(Side) -> Result<T, D::Error>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Clone,
+{
+    let entries = deserializer.deserialize_map(Shorthand::<T> { keys, value: PhantomData })?;
+
+    Ok(move |side| {
+        let fallbacks = fallbacks(side);
+
+        fallbacks
+            .iter()
+            .find_map(|key| entries.iter().find(|(other, _)| other == key))
+            .map(|(_, value)| value.clone())
+            .ok_or_else(|| de::Error::missing_field(fallbacks[0]))
+    })
+}
+
+impl<'de, T: Deserialize<'de> + Clone> Deserialize<'de> for DirMap<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = expand(deserializer, DIR_KEYS)?;
+
+        Ok(Self {
+            west: value(Some(Direction::West))?,
+            east: value(Some(Direction::East))?,
+            south: value(Some(Direction::South))?,
+            north: value(Some(Direction::North))?,
+            down: value(Some(Direction::Down))?,
+            up: value(Some(Direction::Up))?,
+        })
+    }
+}
+
+impl<'de, T: Deserialize<'de> + Clone> Deserialize<'de> for SideMap<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = expand(deserializer, SIDE_KEYS)?;
+
+        Ok(Self {
+            west: value(Some(Direction::West))?,
+            east: value(Some(Direction::East))?,
+            south: value(Some(Direction::South))?,
+            north: value(Some(Direction::North))?,
+            down: value(Some(Direction::Down))?,
+            up: value(Some(Direction::Up))?,
+            none: value(None)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dirs<T: Copy>(map: &DirMap<T>) -> [T; 6] {
+        DIRECTIONS.map(|dir| map[dir])
+    }
+
+    #[test]
+    fn specific_keys_win() {
+        let map: DirMap<i32> = toml::from_str("all = 1\nx = 2\nwest = 3").unwrap();
+        assert_eq!(dirs(&map), [3, 2, 1, 1, 1, 1]);
+
+        let map: DirMap<i32> = toml::from_str("north = 6\nz = 5\nsides = 4").unwrap();
+        assert_eq!(dirs(&map), [4, 4, 4, 6, 5, 5]);
+
+        let map: DirMap<i32> = toml::from_str("all = 1\nsides = 2\ny = 3\nup = 4").unwrap();
+        assert_eq!(dirs(&map), [2, 2, 3, 3, 1, 4]);
+    }
+
+    #[test]
+    fn sides_never_cover_the_ends() {
+        let map = toml::from_str::<DirMap<i32>>("sides = 1\ndown = 2");
+        assert!(map.unwrap_err().to_string().contains("`up`"));
+    }
+
+    #[test]
+    fn none_only_comes_from_all() {
+        let map: SideMap<i32> = toml::from_str("all = 1\nnone = 0\ny = 7").unwrap();
+        assert_eq!(SIDES.map(|side| map[side]), [1, 1, 7, 7, 1, 1, 0]);
+
+        let map = toml::from_str::<SideMap<i32>>("sides = 1\nz = 2");
+        assert!(map.unwrap_err().to_string().contains("`none`"));
+    }
+
+    #[test]
+    fn bad_keys_are_rejected() {
+        assert!(toml::from_str::<DirMap<i32>>("all = 1\nwset = 2").is_err());
+        assert!(toml::from_str::<DirMap<i32>>("all = 1\nnone = 2").is_err());
+        assert!(toml::from_str::<DirMap<i32>>("all = 1\nall = 2").is_err());
+    }
+}
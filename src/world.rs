@@ -1,28 +1,514 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::{HashMap, HashSet}, io::{self, ErrorKind}, ops::{Range, RangeInclusive}, path::Path, sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard}};
 
-use glam::IVec3;
+use glam::{ivec3, IVec2, IVec3};
 
-use crate::{chunk::{Chunk, Mesher}, graphics::Vertex, assets::Pack};
+use crate::{cache::Cache, chunk::{Chunk, Mesh, Mesher}, assets::{BlockId, Pack}, storage::Storage, terrain::{self, Registry, TerrainGenerator, Void}, light};
+
+/// Offsets of a chunk and the 26 chunks around it.
+const NEIGHBORHOOD: [IVec3; 27] = {
+    let mut offsets = [IVec3::ZERO; 27];
+    let mut idx = 0;
+
+    while idx < 27 {
+        offsets[idx] = IVec3::new(idx as i32 % 3 - 1, idx as i32 / 3 % 3 - 1, idx as i32 / 9 - 1);
+        idx += 1;
+    }
+
+    offsets
+};
+
+/// Every location in the box between two corners, both included, in z, y, x
+/// order.
+fn span(min: IVec3, max: IVec3) -> impl Iterator<Item = IVec3> {
+    (min.z..=max.z).flat_map(move |z| (min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec3::new(x, y, z))))
+}
+
+/// Chunks whose padded copy includes the block at `location`.
+pub fn sharing_chunks(location: IVec3) -> impl Iterator<Item = IVec3> {
+    let chunk_loc: IVec3 = location >> 5;
+    let block_loc = location & 31;
+    let min = IVec3::select(block_loc.cmpeq(IVec3::ZERO), IVec3::NEG_ONE, IVec3::ZERO);
+    let max = IVec3::select(block_loc.cmpeq(IVec3::splat(31)), IVec3::ONE, IVec3::ZERO);
+
+    NEIGHBORHOOD
+        .into_iter()
+        .filter(move |offset| offset.cmpge(min).all() && offset.cmple(max).all())
+        .map(move |offset| chunk_loc + offset)
+}
+
+/// Chunks above and below the origin generated in every column searched for a
+/// spawn point, unless the world is not as tall.
+const SPAWN_DEPTH: i32 = 4;
+
+/// Furthest column of chunks from the origin searched for a spawn point.
+const SPAWN_RADIUS: i32 = 4;
+
+/// Highest solid block of every column in a chunk, in world coordinates.
+fn chunk_top(chunk: &Chunk, location: IVec3, column: IVec2) -> Option<i32> {
+    match chunk.uniform() {
+        Some(BlockId::AIR) => None,
+        Some(_) => Some(location.z * 32 + 31),
+        None => (0..32).rev().find(|k| chunk[column.extend(*k)] != BlockId::AIR).map(|k| location.z * 32 + k),
+    }
+}
+
+/// Height map of a column of chunks, indexed by y and then x. Columns without
+/// any block hold `i32::MIN`.
+pub type HeightMap = [[i32; 32]; 32];
 
-#[derive(Default)]
 pub struct World {
-    pub loaded_chunks: HashMap<[i32; 3], Chunk>,
+    pub seed: u64,
+    chunks: Cache,
+
+    /// Highest block of every column, as far as the loaded chunks tell.
+    heights: HashMap<IVec2, Box<HeightMap>>,
+
+    /// Heights blocks may exist at, the top excluded.
+    bounds: Range<i32>,
+
+    generator: Arc<dyn TerrainGenerator>,
+    storage: Option<Arc<Mutex<Storage>>>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::generate(0, Arc::new(Void))
+    }
 }
 
 impl World {
-    pub fn generate(seed: u64) -> Self {
-        Self::default()
+    /// Creates a world kept only in memory, whose chunks are generated from
+    /// `seed` as they are loaded.
+    pub fn generate(seed: u64, generator: Arc<dyn TerrainGenerator>) -> Self {
+        Self {
+            seed,
+            chunks: Cache::default(),
+            heights: HashMap::default(),
+            bounds: i32::MIN..i32::MAX,
+            generator,
+            storage: None,
+        }
+    }
+
+    /// Opens a world saved at `path`, or creates a new one there with `seed`
+    /// and the terrain generator described by `spec`.
+    pub fn open(path: impl AsRef<Path>, seed: u64, spec: &str, terrain: &Registry, pack: &Pack) -> io::Result<Self> {
+        let create = |spec: &str| terrain.create(spec, pack).map_err(|err| io::Error::new(ErrorKind::InvalidInput, err));
+
+        // Check the spec before it ends up saved along with a new world
+        let generator = create(spec)?;
+        let storage = Storage::open(path, seed, spec, &pack.blocks)?;
+
+        let generator = match storage.generator() {
+            stored if stored == spec => generator,
+            stored => create(stored)?,
+        };
+
+        Ok(Self {
+            seed: storage.seed(),
+            chunks: Cache::default(),
+            heights: HashMap::default(),
+            bounds: pack.worldgen.height.bottom..pack.worldgen.height.top,
+            generator,
+            storage: Some(Arc::new(Mutex::new(storage))),
+        })
+    }
+
+    /// Returns a function loading chunks from disk, or generating those never
+    /// saved, which can be called from any thread.
+    pub fn loader(&self, pack: Arc<Pack>) -> impl Fn(IVec3) -> io::Result<Chunk> + Send + Sync + 'static {
+        let storage = self.storage.clone();
+        let generator = self.generator.clone();
+        let seed = self.seed;
+
+        move |location| {
+            let stored = match &storage {
+                Some(storage) => storage.lock().unwrap().load(location)?,
+                None => None,
+            };
+
+            Ok(stored.unwrap_or_else(|| terrain::generate(&*generator, location, seed, &pack)))
+        }
+    }
+
+    pub fn chunk(&self, location: IVec3) -> Option<RwLockReadGuard<'_, Chunk>> {
+        self.chunks.get(location).map(|chunk| chunk.read().unwrap())
     }
 
-    pub fn build_meshes<'a: 'b + 'c, 'b: 'a, 'c: 'a>(&'a self, mesher: &'b mut Mesher, location: IVec3, pack: &'c Pack, distance: i32) -> impl Iterator<Item = (IVec3, Arc<(u32, Vec<Vertex>, Vec<u32>)>)> + 'a + 'b + 'c {
-        self.loaded_chunks
+    pub fn chunk_mut(&mut self, location: IVec3) -> Option<RwLockWriteGuard<'_, Chunk>> {
+        self.chunks.get(location).map(|chunk| chunk.write().unwrap())
+    }
+
+    /// Heights blocks may exist at, the top excluded. Worlds only kept in
+    /// memory have no limits.
+    pub fn bounds(&self) -> Range<i32> {
+        self.bounds.clone()
+    }
+
+    /// Heights of the chunks holding any block within bounds.
+    pub fn chunk_bounds(&self) -> RangeInclusive<i32> {
+        self.bounds.start >> 5..=(self.bounds.end - 1) >> 5
+    }
+
+    pub fn is_loaded(&self, location: IVec3) -> bool {
+        self.chunks.contains(location)
+    }
+
+    /// Whether a chunk at `location` fits in the cached area around the last
+    /// center, as chunks outside of it cannot be loaded.
+    pub fn covers(&self, location: IVec3) -> bool {
+        self.chunks.covers(location)
+    }
+
+    /// Locations of every loaded chunk.
+    pub fn loaded(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.locations()
+    }
+
+    fn store(&self, location: IVec3, chunk: &mut Chunk) -> io::Result<()> {
+        match &self.storage {
+            Some(storage) if chunk.modified => {
+                storage.lock().unwrap().store(location, chunk)?;
+                chunk.modified = false;
+                Ok(())
+            }
+
+            _ => Ok(()),
+        }
+    }
+
+    /// Removes a chunk from the world, saving it first if it was modified.
+    pub fn unload(&mut self, location: IVec3) -> io::Result<()> {
+        match self.chunks.remove(location) {
+            Some(chunk) => {
+                self.forget_heights(location);
+                self.store(location, &mut chunk.write().unwrap())
+            }
+
+            None => Ok(()),
+        }
+    }
+
+    /// Moves the cached area along with the camera, unloading the chunks it
+    /// leaves behind.
+    pub fn recenter(&mut self, center: IVec3) -> io::Result<()> {
+        for (location, chunk) in self.chunks.recenter(center) {
+            self.forget_heights(location);
+            self.store(location, &mut chunk.write().unwrap())?;
+        }
+
+        Ok(())
+    }
+
+    /// Height of the highest block at `x`, `y` among the loaded chunks, if any.
+    pub fn height(&self, x: i32, y: i32) -> Option<i32> {
+        let location = IVec2::new(x, y);
+        let column: IVec2 = location >> 5;
+        let IVec2 { x: i, y: j } = location & 31;
+
+        self.heights
+            .get(&column)
+            .map(|heights| heights[j as usize][i as usize])
+            .filter(|height| *height != i32::MIN)
+    }
+
+    /// Height map of a column of chunks, if any of them is loaded.
+    pub fn height_map(&self, column: IVec2) -> Option<&HeightMap> {
+        self.heights.get(&column).map(|heights| &**heights)
+    }
+
+    /// Highest block of a column at or below the chunk at `location`, looking
+    /// through the loaded chunks only.
+    fn column_top(&self, mut location: IVec3, column: IVec2) -> i32 {
+        while self.chunks.covers(location) {
+            if let Some(top) = self.chunk(location).and_then(|chunk| chunk_top(&chunk, location, column)) {
+                return top;
+            }
+
+            location.z -= 1;
+        }
+
+        i32::MIN
+    }
+
+    /// Raises the height map with the blocks of a newly loaded chunk.
+    fn add_heights(&mut self, location: IVec3) {
+        let Some(chunk) = self.chunks.get(location).cloned() else {
+            return;
+        };
+
+        let chunk = chunk.read().unwrap();
+        let heights = self
+            .heights
+            .entry(location.truncate())
+            .or_insert_with(|| Box::new([[i32::MIN; 32]; 32]));
+
+        for (j, row) in heights.iter_mut().enumerate() {
+            for (i, height) in row.iter_mut().enumerate() {
+                if let Some(top) = chunk_top(&chunk, location, IVec2::new(i as i32, j as i32)) {
+                    *height = (*height).max(top);
+                }
+            }
+        }
+    }
+
+    /// Lowers the height map where its highest blocks were in a chunk no
+    /// longer loaded.
+    fn forget_heights(&mut self, location: IVec3) {
+        let Some(mut heights) = self.heights.remove(&location.truncate()) else {
+            return;
+        };
+
+        for (j, row) in heights.iter_mut().enumerate() {
+            for (i, height) in row.iter_mut().enumerate() {
+                if *height >> 5 == location.z {
+                    *height = self.column_top(location - IVec3::Z, IVec2::new(i as i32, j as i32));
+                }
+            }
+        }
+
+        if heights.iter().flatten().any(|height| *height != i32::MIN) {
+            self.heights.insert(location.truncate(), heights);
+        }
+    }
+
+    /// Keeps the height map in step with a block that changed.
+    fn update_height(&mut self, location: IVec3, block: BlockId) {
+        let column: IVec2 = location.truncate() >> 5;
+        let block_loc = location.truncate() & 31;
+
+        let top = match self.heights.get(&column) {
+            Some(heights) => heights[block_loc.y as usize][block_loc.x as usize],
+            None => i32::MIN,
+        };
+
+        let top = if block != BlockId::AIR {
+            top.max(location.z)
+        } else if location.z == top {
+            let chunk_loc: IVec3 = location >> 5;
+            self.column_top(chunk_loc, block_loc)
+        } else {
+            return;
+        };
+
+        let heights = self
+            .heights
+            .entry(column)
+            .or_insert_with(|| Box::new([[i32::MIN; 32]; 32]));
+
+        heights[block_loc.y as usize][block_loc.x as usize] = top;
+    }
+
+    /// Adds a chunk to the world, lighting it and invalidating the meshes
+    /// around it. Chunks outside the cached area or the bounds are dropped.
+    pub fn insert(&mut self, location: IVec3, chunk: Chunk, pack: &Pack) {
+        if !self.chunk_bounds().contains(&location.z) || self.chunks.insert(location, chunk).is_err() {
+            return;
+        }
+
+        self.add_heights(location);
+
+        light::light_chunk(self, location, pack);
+
+        for offset in NEIGHBORHOOD {
+            if offset != IVec3::ZERO {
+                if let Some(mut neighbor) = self.chunk_mut(location + offset) {
+                    neighbor.touch();
+                }
+            }
+        }
+    }
+
+    /// Block at a world location, if its chunk is loaded.
+    pub fn get_block(&self, location: IVec3) -> Option<BlockId> {
+        let chunk_loc: IVec3 = location >> 5;
+        self.chunk(chunk_loc).map(|chunk| chunk[location])
+    }
+
+    /// Places a block at a world location. Returns whether anything changed,
+    /// which is never the case if the chunk is not loaded or out of bounds.
+    pub fn set_block(&mut self, location: IVec3, block: BlockId, pack: &Pack) -> bool {
+        self.edit_region(location, location, pack, |_| Some(block)) > 0
+    }
+
+    /// Fills the box between two corners, both included, returning how many
+    /// blocks changed. Unloaded chunks are left alone.
+    pub fn fill_region(&mut self, min: IVec3, max: IVec3, block: BlockId, pack: &Pack) -> usize {
+        self.edit_region(min, max, pack, |_| Some(block))
+    }
+
+    /// Replaces every `from` block in the box between two corners, both
+    /// included, returning how many blocks changed.
+    pub fn replace_in_region(&mut self, min: IVec3, max: IVec3, from: BlockId, to: BlockId, pack: &Pack) -> usize {
+        self.edit_region(min, max, pack, |block| (block == from).then_some(to))
+    }
+
+    /// Loaded blocks in the box between two corners, both included, in z, y, x
+    /// order within each chunk.
+    pub fn blocks(&self, min: IVec3, max: IVec3) -> impl Iterator<Item = (IVec3, BlockId)> + '_ {
+        let (min, max) = (min.min(max), min.max(max));
+        let (first, last): (IVec3, IVec3) = (min >> 5, max >> 5);
+
+        span(first, last).flat_map(move |chunk_loc| {
+            let (lo, hi) = ((chunk_loc * 32).max(min), (chunk_loc * 32 + 31).min(max));
+
+            match self.chunk(chunk_loc) {
+                Some(chunk) => span(lo, hi).map(|location| (location, chunk[location])).collect(),
+                None => Vec::new(),
+            }
+        })
+    }
+
+    /// Applies `edit` to every loaded block in a box, one chunk at a time,
+    /// then invalidates the meshes and relights around whatever changed.
+    /// Blocks out of bounds are left alone.
+    fn edit_region(&mut self, min: IVec3, max: IVec3, pack: &Pack, mut edit: impl FnMut(BlockId) -> Option<BlockId>) -> usize {
+        let (mut min, mut max) = (min.min(max), min.max(max));
+        min.z = min.z.max(self.bounds.start);
+        max.z = max.z.min(self.bounds.end - 1);
+
+        if min.z > max.z {
+            return 0;
+        }
+        let (first, last): (IVec3, IVec3) = (min >> 5, max >> 5);
+        let mut changed = Vec::new();
+
+        for chunk_loc in span(first, last) {
+            let (lo, hi) = ((chunk_loc * 32).max(min), (chunk_loc * 32 + 31).min(max));
+
+            let Some(mut chunk) = self.chunk_mut(chunk_loc) else {
+                continue;
+            };
+
+            for location in span(lo, hi) {
+                if let Some(block) = edit(chunk[location]) {
+                    if chunk.place(location, block) {
+                        changed.push(location);
+                    }
+                }
+            }
+        }
+
+        for location in &changed {
+            let block = self.get_block(*location).unwrap_or_default();
+            self.update_height(*location, block);
+        }
+
+        let touched = changed
+            .iter()
+            .flat_map(|location| sharing_chunks(*location))
+            .collect::<HashSet<_>>();
+
+        for chunk_loc in touched {
+            if let Some(mut neighbor) = self.chunk_mut(chunk_loc) {
+                neighbor.touch();
+            }
+        }
+
+        light::update(self, &changed, pack);
+        changed.len()
+    }
+
+    /// Looks for a spot to stand on nearest to the origin in the terrain
+    /// generated from `seed`: solid and opaque ground, so neither water nor
+    /// leaves, with two blocks of air above. Chunks are generated into a world
+    /// of their own rather than loaded into this one, so the spot only depends
+    /// on the seed and not on whatever was built since. Returns the location
+    /// of the lower air block, if any.
+    pub fn find_spawn(&self, seed: u64, pack: &Pack) -> Option<IVec3> {
+        let mut scratch = Self::generate(seed, self.generator.clone());
+        scratch.bounds = self.bounds();
+
+        let bounds = self.chunk_bounds();
+        let (bottom, top) = ((*bounds.start()).max(-SPAWN_DEPTH), (*bounds.end()).min(SPAWN_DEPTH - 1));
+        let mut nearest: Option<IVec3> = None;
+
+        for radius in 0..=SPAWN_RADIUS {
+            let mut ground = false;
+
+            for y in -radius..=radius {
+                for x in -radius..=radius {
+                    if x.abs().max(y.abs()) != radius {
+                        continue;
+                    }
+
+                    // Only blocks and heights are needed, so nothing is lit
+                    for z in bottom..=top {
+                        let location = ivec3(x, y, z);
+
+                        if scratch.chunks.insert(location, terrain::generate(&*self.generator, location, seed, pack)).is_ok() {
+                            scratch.add_heights(location);
+                        }
+                    }
+
+                    ground |= scratch.height_map(IVec2::new(x, y)).is_some();
+
+                    let spots = span(ivec3(x * 32, y * 32, 0), ivec3(x * 32 + 31, y * 32 + 31, 0)).filter_map(|column| {
+                        let ground = column.truncate().extend(scratch.height(column.x, column.y)?);
+                        let block = &pack.blocks[scratch.get_block(ground)?];
+                        let clear = (1..=2).all(|dz| scratch.get_block(ground + IVec3::Z * dz) == Some(BlockId::AIR));
+
+                        (block.solid && block.opaque() && clear).then_some(ground + IVec3::Z)
+                    });
+
+                    for spot in spots {
+                        if nearest.map_or(true, |nearest| spot.truncate().length_squared() < nearest.truncate().length_squared()) {
+                            nearest = Some(spot);
+                        }
+                    }
+                }
+            }
+
+            // Rings of chunks are squares, so a spot further out may still be
+            // nearer than one already found
+            if nearest.is_some_and(|nearest| nearest.truncate().length_squared() <= (radius * 32).pow(2)) {
+                break;
+            }
+
+            // Worlds without any ground near the origin, such as void ones,
+            // are not searched any further
+            if !ground {
+                break;
+            }
+        }
+
+        nearest
+    }
+
+    /// The chunk at `location` and the 26 around it, ordered as expected by
+    /// [`Padded::from_neighborhood`](crate::chunk::Padded::from_neighborhood).
+    fn neighborhood(&self, location: IVec3) -> [Option<Arc<RwLock<Chunk>>>; 27] {
+        NEIGHBORHOOD.map(|offset| self.chunks.get(location + offset).cloned())
+    }
+
+    /// Writes every chunk modified since it was loaded back to disk.
+    pub fn save(&mut self) -> io::Result<()> {
+        for (location, chunk) in self.chunks.iter() {
+            self.store(location, &mut chunk.write().unwrap())?;
+        }
+
+        Ok(())
+    }
+
+    /// Queues meshes for the chunks within `distance` of `location` whose
+    /// contents changed, nearest first, and returns those already built.
+    pub fn build_meshes<'a>(&'a self, mesher: &'a mut Mesher, location: IVec3, distance: i32) -> impl Iterator<Item = (IVec3, Arc<Mesh>)> + 'a {
+        let center: IVec3 = location >> 5;
+
+        mesher.collect();
+        mesher.retain(|pos| self.chunks.contains(pos));
+
+        let in_range = move |(pos, chunk): (IVec3, &'a Arc<RwLock<Chunk>>)| {
+            (center.distance_squared(pos) < distance * distance).then(|| (pos, chunk.read().unwrap().nonce))
+        };
+
+        for (pos, nonce) in self.chunks.iter().filter_map(in_range) {
+            mesher.request(pos, nonce, || self.neighborhood(pos), center.distance_squared(pos) as i64);
+        }
+
+        self.chunks
             .iter()
-            .filter(move |(pos, _)| {
-                let chunk_loc: IVec3 = location.clone() >> 5;
-                chunk_loc.distance_squared(IVec3::from_array(**pos)) < distance*distance
-            })
-            .map(|(pos, chunk)| {
-                (IVec3::from_slice(pos), mesher.build_mesh(chunk, pos.clone(), pack).clone())
-            })
+            .filter_map(in_range)
+            .filter_map(move |(pos, nonce)| Some((pos, mesher.cached(pos, nonce)?)))
     }
 }